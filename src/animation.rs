@@ -0,0 +1,703 @@
+use std::rc::{Rc, Weak};
+use std::cell::{RefCell, Cell};
+use std::time::{Duration, Instant};
+
+use crate::core::Widget;
+use crate::draw::Color;
+use crate::dimension::*;
+use crate::timer::*;
+
+/// Values that an `Animation` can move between
+pub trait Interpolate : Clone
+{
+	/// the value `t` of the way from `self` to `to`, where
+	/// `t` is usually between 0 and 1 but may overshoot
+	fn interpolate(&self, to: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64
+{
+	fn interpolate(&self, to: &f64, t: f64) -> f64
+	{
+		self + (to-self)*t
+	}
+}
+
+impl Interpolate for f32
+{
+	fn interpolate(&self, to: &f32, t: f64) -> f32
+	{
+		(*self as f64).interpolate(&(*to as f64), t) as f32
+	}
+}
+
+impl Interpolate for i32
+{
+	fn interpolate(&self, to: &i32, t: f64) -> i32
+	{
+		(*self as f64).interpolate(&(*to as f64), t).round() as i32
+	}
+}
+
+impl Interpolate for u32
+{
+	fn interpolate(&self, to: &u32, t: f64) -> u32
+	{
+		(*self as f64).interpolate(&(*to as f64), t).round().max(0.0) as u32
+	}
+}
+
+impl Interpolate for Color
+{
+	fn interpolate(&self, to: &Color, t: f64) -> Color
+	{
+		self.mix(to, t)
+	}
+}
+
+impl Interpolate for Point
+{
+	fn interpolate(&self, to: &Point, t: f64) -> Point
+	{
+		Point { x: self.x.interpolate(&to.x, t), y: self.y.interpolate(&to.y, t) }
+	}
+}
+
+impl Interpolate for Size
+{
+	fn interpolate(&self, to: &Size, t: f64) -> Size
+	{
+		Size
+		{
+			width: self.width.interpolate(&to.width, t),
+			height: self.height.interpolate(&to.height, t),
+		}
+	}
+}
+
+impl Interpolate for Rectangle
+{
+	fn interpolate(&self, to: &Rectangle, t: f64) -> Rectangle
+	{
+		Rectangle
+		{
+			pos: self.pos.interpolate(&to.pos, t),
+			size: self.size.interpolate(&to.size, t),
+		}
+	}
+}
+
+/// How the progress of an animation follows time
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing
+{
+	Linear,
+	InQuad,
+	OutQuad,
+	InOutQuad,
+	InCubic,
+	OutCubic,
+	InOutCubic,
+	InSine,
+	OutSine,
+	InOutSine,
+	/// overshoots the end value a little and comes back
+	OutBack,
+}
+
+impl Easing
+{
+	/// the progress for the fraction `t` of the duration
+	pub fn apply(&self, t: f64) -> f64
+	{
+		use std::f64::consts::PI;
+		let t = t.max(0.0).min(1.0);
+		match *self
+		{
+			Easing::Linear => t,
+			Easing::InQuad => t*t,
+			Easing::OutQuad => t*(2.0-t),
+			Easing::InOutQuad =>
+				if t < 0.5 { 2.0*t*t } else { -1.0 + (4.0-2.0*t)*t },
+			Easing::InCubic => t*t*t,
+			Easing::OutCubic => { let u = t-1.0; u*u*u + 1.0 },
+			Easing::InOutCubic =>
+				if t < 0.5 { 4.0*t*t*t }
+				else { let u = 2.0*t-2.0; 0.5*u*u*u + 1.0 },
+			Easing::InSine => 1.0 - (t*PI/2.0).cos(),
+			Easing::OutSine => (t*PI/2.0).sin(),
+			Easing::InOutSine => 0.5 * (1.0 - (t*PI).cos()),
+			Easing::OutBack =>
+			{
+				let s = 1.70158;
+				let u = t-1.0;
+				u*u*((s+1.0)*u + s) + 1.0
+			},
+		}
+	}
+}
+
+impl Default for Easing
+{
+	fn default() -> Easing
+	{
+		Easing::Linear
+	}
+}
+
+/// Something that can be run with `GraphicalDetails::animate`:
+/// an `Animation` or a group of them
+pub trait Animate
+{
+	fn duration(&self) -> Duration;
+	/// move to `elapsed` after the start, clamped to the duration
+	fn advance(&self, elapsed: Duration);
+	/// forget having finished, so the animation can run again
+	fn reset(&self);
+}
+
+/// the finished callbacks of an animation, called once per run
+struct Finished
+{
+	done: Cell<bool>,
+	callbacks: RefCell<Vec<Box<FnMut()>>>,
+}
+
+impl Finished
+{
+	fn new() -> Finished
+	{
+		Finished
+		{
+			done: Cell::new(false),
+			callbacks: RefCell::new(vec!()),
+		}
+	}
+
+	fn reached(&self)
+	{
+		if self.done.replace(true) { return; }
+		for c in self.callbacks.borrow_mut().iter_mut()
+			{ c(); }
+	}
+}
+
+/// Moves a value from one end to the other over a duration,
+/// handing each step to a callback
+pub struct Animation<T: Interpolate>
+{
+	from: T,
+	to: T,
+	duration: Duration,
+	easing: Cell<Easing>,
+	apply: RefCell<Box<FnMut(T)>>,
+	targets: RefCell<Vec<Weak<Widget>>>,
+	finished: Finished,
+}
+
+impl<T: Interpolate> Animation<T>
+{
+	/// an animation from `from` to `to` that calls `apply` with each
+	/// intermediate value
+	pub fn new<F: 'static+FnMut(T)>(from: T, to: T, duration: Duration, apply: F)
+		-> Rc<Animation<T>>
+	{
+		Rc::new(
+			Animation
+			{
+				from,
+				to,
+				duration,
+				easing: Cell::new(Easing::Linear),
+				apply: RefCell::new(Box::new(apply)),
+				targets: RefCell::new(vec!()),
+				finished: Finished::new(),
+			}
+		)
+	}
+
+	pub fn set_easing(&self, easing: Easing)
+	{
+		self.easing.set(easing);
+	}
+	pub fn easing(&self) -> Easing
+	{
+		self.easing.get()
+	}
+
+	/// repaint `widget` after every step
+	pub fn add_target(&self, widget: &Rc<Widget>)
+	{
+		self.targets.borrow_mut().push(Rc::downgrade(widget));
+	}
+
+	/// call `cb` when the animation reaches its end
+	pub fn on_finished<F: 'static+FnMut()>(&self, cb: F)
+	{
+		self.finished.callbacks.borrow_mut().push(Box::new(cb));
+	}
+
+	/// the value at the fraction `t` of the duration
+	pub fn value_at(&self, t: f64) -> T
+	{
+		self.from.interpolate(&self.to, self.easing.get().apply(t))
+	}
+}
+
+impl<T: Interpolate> Animate for Animation<T>
+{
+	fn duration(&self) -> Duration
+	{
+		self.duration
+	}
+
+	fn advance(&self, elapsed: Duration)
+	{
+		if self.finished.done.get() { return; }
+
+		let t = if elapsed >= self.duration
+		{
+			1.0
+		}
+		else
+		{
+			duration_secs(elapsed) / duration_secs(self.duration)
+		};
+
+		(self.apply.borrow_mut())(self.value_at(t));
+		for w in self.targets.borrow().iter()
+		{
+			if let Some(w) = w.upgrade()
+				{ w.update(); }
+		}
+
+		if t >= 1.0
+			{ self.finished.reached(); }
+	}
+
+	fn reset(&self)
+	{
+		self.finished.done.set(false);
+	}
+}
+
+/// Runs animations one after another
+pub struct SequentialGroup
+{
+	children: RefCell<Vec<Rc<Animate>>>,
+	finished: Finished,
+}
+
+impl SequentialGroup
+{
+	pub fn new() -> Rc<SequentialGroup>
+	{
+		Rc::new(
+			SequentialGroup
+			{
+				children: RefCell::new(vec!()),
+				finished: Finished::new(),
+			}
+		)
+	}
+
+	pub fn add(&self, anim: Rc<Animate>)
+	{
+		self.children.borrow_mut().push(anim);
+	}
+
+	/// wait for `pause` before going on to the next animation
+	pub fn add_pause(&self, pause: Duration)
+	{
+		self.add(Animation::new(0.0, 1.0, pause, |_: f64| {}));
+	}
+
+	pub fn on_finished<F: 'static+FnMut()>(&self, cb: F)
+	{
+		self.finished.callbacks.borrow_mut().push(Box::new(cb));
+	}
+}
+
+impl Animate for SequentialGroup
+{
+	fn duration(&self) -> Duration
+	{
+		self.children.borrow().iter()
+			.fold(Duration::from_secs(0), |d, c| d + c.duration())
+	}
+
+	fn advance(&self, elapsed: Duration)
+	{
+		if self.finished.done.get() { return; }
+
+		let children = self.children.borrow().clone();
+		let mut start = Duration::from_secs(0);
+		for c in &children
+		{
+			// later animations haven't started yet
+			if elapsed < start { break; }
+			c.advance(elapsed - start);
+			start += c.duration();
+		}
+
+		if elapsed >= start
+			{ self.finished.reached(); }
+	}
+
+	fn reset(&self)
+	{
+		self.finished.done.set(false);
+		for c in self.children.borrow().iter()
+			{ c.reset(); }
+	}
+}
+
+/// Runs animations at the same time
+pub struct ParallelGroup
+{
+	children: RefCell<Vec<Rc<Animate>>>,
+	finished: Finished,
+}
+
+impl ParallelGroup
+{
+	pub fn new() -> Rc<ParallelGroup>
+	{
+		Rc::new(
+			ParallelGroup
+			{
+				children: RefCell::new(vec!()),
+				finished: Finished::new(),
+			}
+		)
+	}
+
+	pub fn add(&self, anim: Rc<Animate>)
+	{
+		self.children.borrow_mut().push(anim);
+	}
+
+	pub fn on_finished<F: 'static+FnMut()>(&self, cb: F)
+	{
+		self.finished.callbacks.borrow_mut().push(Box::new(cb));
+	}
+}
+
+impl Animate for ParallelGroup
+{
+	fn duration(&self) -> Duration
+	{
+		self.children.borrow().iter()
+			.map(|c| c.duration())
+			.max()
+			.unwrap_or(Duration::from_secs(0))
+	}
+
+	fn advance(&self, elapsed: Duration)
+	{
+		if self.finished.done.get() { return; }
+
+		let children = self.children.borrow().clone();
+		for c in &children
+			{ c.advance(elapsed); }
+
+		if elapsed >= self.duration()
+			{ self.finished.reached(); }
+	}
+
+	fn reset(&self)
+	{
+		self.finished.done.set(false);
+		for c in self.children.borrow().iter()
+			{ c.reset(); }
+	}
+}
+
+fn duration_secs(d: Duration) -> f64
+{
+	d.as_secs() as f64 + d.subsec_nanos() as f64 * 1e-9
+}
+
+/// A handle to a running animation
+#[derive(Clone)]
+pub struct AnimationHandle
+{
+	running: Rc<Cell<bool>>,
+}
+
+impl AnimationHandle
+{
+	/// stop the animation where it is, without finishing it
+	pub fn stop(&self)
+	{
+		self.running.set(false);
+	}
+
+	pub fn is_running(&self) -> bool
+	{
+		self.running.get()
+	}
+}
+
+struct Running
+{
+	anim: Rc<Animate>,
+	start: Instant,
+	running: Rc<Cell<bool>>,
+}
+
+/// The animations being run by the event loop, stepped
+/// by a timer while there are any
+pub(crate) struct Animations
+{
+	running: RefCell<Vec<Running>>,
+	tick: RefCell<Option<Timer>>,
+}
+
+/// the time between animation frames
+const FRAME_INTERVAL_MS : u64 = 16;
+
+impl Animations
+{
+	pub(crate) fn new() -> Rc<Animations>
+	{
+		Rc::new(
+			Animations
+			{
+				running: RefCell::new(vec!()),
+				tick: RefCell::new(None),
+			}
+		)
+	}
+
+	pub(crate) fn start(this: &Rc<Animations>, timers: &Timers, anim: Rc<Animate>)
+		-> AnimationHandle
+	{
+		anim.reset();
+		anim.advance(Duration::from_secs(0));
+
+		let running = Rc::new(Cell::new(true));
+		this.running.borrow_mut().push(
+			Running
+			{
+				anim,
+				start: Instant::now(),
+				running: running.clone(),
+			}
+		);
+
+		let mut tick = this.tick.borrow_mut();
+		if tick.is_none()
+		{
+			let weak = Rc::downgrade(this);
+			let interval = Duration::from_millis(FRAME_INTERVAL_MS);
+			*tick = Some(
+				timers.add(
+					interval,
+					Some(interval),
+					Box::new(
+						move ||
+						{
+							if let Some(a) = weak.upgrade()
+								{ a.step(); }
+						}
+					),
+				)
+			);
+		}
+
+		AnimationHandle { running }
+	}
+
+	/// advance every running animation to now
+	fn step(&self)
+	{
+		// callbacks may start new animations, so don't
+		// hold the list while running them
+		let current = std::mem::replace(&mut *self.running.borrow_mut(), vec!());
+		let now = Instant::now();
+
+		let mut still_running = vec!();
+		for r in current
+		{
+			if !r.running.get() { continue; }
+
+			let elapsed = now - r.start;
+			r.anim.advance(elapsed);
+			if elapsed >= r.anim.duration()
+			{
+				r.running.set(false);
+			}
+			else
+			{
+				still_running.push(r);
+			}
+		}
+
+		let mut running = self.running.borrow_mut();
+		still_running.extend(running.drain(..));
+		*running = still_running;
+
+		if running.is_empty()
+		{
+			if let Some(t) = self.tick.borrow_mut().take()
+				{ t.cancel(); }
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn ms(ms: u64) -> Duration
+	{
+		Duration::from_millis(ms)
+	}
+
+	/// an animation from 0 to 100 that records every value
+	fn recorded(duration: Duration) -> (Rc<Animation<f64>>, Rc<RefCell<Vec<f64>>>)
+	{
+		let values = Rc::new(RefCell::new(vec!()));
+		let v = values.clone();
+		(Animation::new(0.0, 100.0, duration, move |x| v.borrow_mut().push(x)), values)
+	}
+
+	fn counter() -> (Rc<Cell<u32>>, impl FnMut())
+	{
+		let n = Rc::new(Cell::new(0));
+		let c = n.clone();
+		(n, move || c.set(c.get()+1))
+	}
+
+	fn close(a: f64, b: f64) -> bool
+	{
+		(a-b).abs() < 1e-9
+	}
+
+	#[test]
+	fn easing_endpoints()
+	{
+		let all = [
+			Easing::Linear, Easing::InQuad, Easing::OutQuad, Easing::InOutQuad,
+			Easing::InCubic, Easing::OutCubic, Easing::InOutCubic,
+			Easing::InSine, Easing::OutSine, Easing::InOutSine, Easing::OutBack,
+		];
+		for e in &all
+		{
+			assert!(close(e.apply(0.0), 0.0), "{:?} at 0", e);
+			assert!(close(e.apply(1.0), 1.0), "{:?} at 1", e);
+			// out of range times are clamped
+			assert!(close(e.apply(-1.0), 0.0), "{:?} before 0", e);
+			assert!(close(e.apply(2.0), 1.0), "{:?} after 1", e);
+		}
+		assert!(close(Easing::InOutQuad.apply(0.5), 0.5));
+		assert!(Easing::OutBack.apply(0.8) > 1.0);
+	}
+
+	#[test]
+	fn animation_steps_and_finishes_once()
+	{
+		let (a, values) = recorded(ms(100));
+		let (finished, f) = counter();
+		a.on_finished(f);
+
+		a.advance(ms(0));
+		a.advance(ms(50));
+		assert_eq!(finished.get(), 0);
+		a.advance(ms(100));
+		a.advance(ms(150));
+		assert_eq!(*values.borrow(), vec!(0.0, 50.0, 100.0));
+		assert_eq!(finished.get(), 1);
+
+		a.reset();
+		a.advance(ms(200));
+		assert_eq!(finished.get(), 2);
+	}
+
+	#[test]
+	fn easing_is_applied()
+	{
+		let (a, values) = recorded(ms(100));
+		a.set_easing(Easing::InQuad);
+		a.advance(ms(50));
+		assert!(close(values.borrow()[0], 25.0));
+	}
+
+	#[test]
+	fn sequential_group()
+	{
+		let (first, first_values) = recorded(ms(100));
+		let (second, second_values) = recorded(ms(100));
+		let g = SequentialGroup::new();
+		g.add(first);
+		g.add_pause(ms(50));
+		g.add(second);
+		let (finished, f) = counter();
+		g.on_finished(f);
+		assert_eq!(g.duration(), ms(250));
+
+		g.advance(ms(50));
+		assert_eq!(*first_values.borrow(), vec!(50.0));
+		assert!(second_values.borrow().is_empty());
+
+		// jumping past the first still finishes it
+		g.advance(ms(200));
+		assert_eq!(*first_values.borrow(), vec!(50.0, 100.0));
+		assert_eq!(*second_values.borrow(), vec!(50.0));
+		assert_eq!(finished.get(), 0);
+
+		g.advance(ms(250));
+		g.advance(ms(300));
+		assert_eq!(*second_values.borrow(), vec!(50.0, 100.0));
+		assert_eq!(finished.get(), 1);
+	}
+
+	#[test]
+	fn parallel_group()
+	{
+		let (short, short_values) = recorded(ms(100));
+		let (long, long_values) = recorded(ms(200));
+		let g = ParallelGroup::new();
+		g.add(short);
+		g.add(long);
+		let (finished, f) = counter();
+		g.on_finished(f);
+		assert_eq!(g.duration(), ms(200));
+
+		g.advance(ms(100));
+		assert_eq!(*short_values.borrow(), vec!(100.0));
+		assert_eq!(*long_values.borrow(), vec!(50.0));
+		assert_eq!(finished.get(), 0);
+
+		g.advance(ms(200));
+		g.advance(ms(250));
+		assert_eq!(*short_values.borrow(), vec!(100.0));
+		assert_eq!(*long_values.borrow(), vec!(50.0, 100.0));
+		assert_eq!(finished.get(), 1);
+	}
+
+	#[test]
+	fn group_reset_runs_again()
+	{
+		let (a, values) = recorded(ms(100));
+		let g = SequentialGroup::new();
+		g.add(a);
+		g.advance(ms(100));
+		g.reset();
+		g.advance(ms(100));
+		assert_eq!(*values.borrow(), vec!(100.0, 100.0));
+	}
+
+	#[test]
+	fn interpolate_values()
+	{
+		assert_eq!(0i32.interpolate(&10, 0.25), 3);
+		assert_eq!(10u32.interpolate(&0, 2.0), 0);
+		assert_eq!(
+			Rectangle::coords(0, 0, 10, 10).interpolate(&Rectangle::coords(10, 20, 30, 40), 0.5),
+			Rectangle::coords(5, 10, 20, 25)
+		);
+	}
+}
@@ -18,6 +18,6 @@ fn main()
 	label.set_image(icon);
 	label.set_geometry(oakland::Rectangle::coords(0, 0, 128, 128));
 
-	g.exec();
+	g.exec().unwrap();
 }
 
@@ -12,6 +12,6 @@ fn main()
 	tabs.add("Settings".into());
 	tabs.set_geometry(oakland::Rectangle::coords(0, 0, 1000, 30));
 
-	g.exec();
+	g.exec().unwrap();
 }
 
@@ -19,68 +19,14 @@ impl Widget for Container
 		&mut self.widget
 	}
 
-	fn setup_children(&self, det: Rc<RefCell<GraphicalDetails>>)
+	fn children(&self) -> Vec<Rc<Widget>>
 	{
-		for b in self.children.borrow_mut().iter_mut()
-		{
-			b.setup(det.clone());
-		}
+		self.children.borrow().clone()
 	}
 
-	fn draw(&self, c: &mut cairo::Cairo)
-	{
-		use std::borrow::Borrow;
-
-		for w in self.children.borrow().iter()
-		{
-			let wrect = w.rectangle();
-			c.save();
-			c.translate(wrect.x() as f64, wrect.y() as f64);
-			c.rectangle(0.0, 0.0, wrect.width() as f64, wrect.height() as f64);
-			c.clip();
-
-			let w = w.as_ref().borrow();
-			w.draw(c);
-			c.restore();
-		}
-	}
-
-	fn child_at(&self, pt: &Point) -> Option<Rc<Widget>>
-	{
-		for w in self.children.borrow().iter()
-		{
-			if w.rectangle().contains(pt)
-			{
-				return Some(w.clone());
-			}
-		}
-		None
-	}
 	fn resized(&self, _sz: Size)
 	{
 	}
-	fn mouse_event(&self, e: MouseEvent, pos: &Point)
-	{
-		let b = self.child_at(pos);
-		if b.is_none() { return; }
-		let b = b.unwrap();
-
-		let mut child = None;
-
-		for w in self.children.borrow().iter()
-		{
-			let w2: Rc<Widget> = w.clone();
-			if Rc::ptr_eq(&w2, &b)
-			{
-				child = Some(w2);
-				break;
-			}
-		}
-		if let Some(w) = child
-		{
-			w.mouse_event(e, &w.pt_from_parent(*pos));
-		}
-	}
 }
 
 pub trait MaybeRc
@@ -126,6 +72,8 @@ impl Container
 		M::W: Widget + 'static
 	{
 		let b = widget.convert();
+		register_widget(&b);
+		set_parent(b.as_widget(), &self.widget);
 		if let Some(c) = self.det()
 		{
 			b.setup(c);
@@ -152,11 +100,10 @@ impl Container
 			}
 		}
 
-		eprintln!("********* REMOVEDing");
 		if let Some(idx) = remove_at
 		{
-			eprintln!("********* REMOVED AT {}", idx);
-			self.children.borrow_mut().remove(idx);
+			let removed = self.children.borrow_mut().remove(idx);
+			removed.as_widget().clear_parent();
 			self.repaint();
 		}
 	}
@@ -1,13 +1,21 @@
 use std;
 
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::cell::{RefCell, Cell};
 use std::borrow::Borrow;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::draw::Color;
 use crate::dimension::*;
 use crate::queue;
+use crate::keyboard::*;
+use crate::mouse::*;
+use crate::timer::*;
+use crate::animation::*;
+use crate::error::{Error, Result};
+use crate::executor::*;
+use crate::signal::*;
 use libc::{c_uchar, c_int};
 
 use image::GenericImage;
@@ -55,6 +63,12 @@ extern
 		stride: c_int
 	) -> *mut libc::c_void;
 
+	fn cairo_image_surface_create(
+		format: c_int,
+		width: c_int,
+		height: c_int,
+	) -> *mut libc::c_void;
+
 	fn cairo_xcb_surface_set_size(
 		surface: *mut libc::c_void,
 		width: c_int,
@@ -69,7 +83,7 @@ fn surface_from_x(
 	drawable : u32,
 	width : i32,
 	height : i32
-) -> cairo::surface::Surface
+) -> Result<cairo::surface::Surface>
 {
 /*
 	let opaque = unsafe { cairo_xcb_surface_create(
@@ -97,14 +111,26 @@ fn surface_from_x(
 	let mut s = cairo::surface::Surface { opaque : opaque };
 	match s.status()
 	{
-		cairo::Status::Success => {},
-		s => panic!("error creating cairo surface {:?}", s),
+		cairo::Status::Success => Ok(s),
+		s => Err(Error::Cairo(s)),
+	}
+}
+
+/// an ARGB32 surface in client memory
+fn image_surface(width: i32, height: i32)
+	-> Result<cairo::surface::Surface>
+{
+	let opaque = unsafe { cairo_image_surface_create(0, width, height) };
+	let mut s = cairo::surface::Surface { opaque : opaque };
+	match s.status()
+	{
+		cairo::Status::Success => Ok(s),
+		s => Err(Error::Cairo(s)),
 	}
-	s
 }
 
 unsafe fn surface_from_img(im: &image::DynamicImage)
-	-> cairo::surface::Surface
+	-> Result<cairo::surface::Surface>
 {
 	let w = im.width();
 	let h = im.height();
@@ -147,47 +173,93 @@ unsafe fn surface_from_img(im: &image::DynamicImage)
 	let mut s = cairo::surface::Surface { opaque : opaque };
 	match s.status()
 	{
-		cairo::Status::Success => {},
-		s => panic!("error creating cairo surface {:?}", s),
+		cairo::Status::Success => Ok(s),
+		s => Err(Error::Cairo(s)),
 	}
+}
 
-	s
+/// The visual and render format that windows are made with, and
+/// the format for pixmaps with an alpha channel if there is one
+#[derive(Copy, Clone)]
+struct WindowFormats
+{
+	depth: u8,
+	visual: u32,
+	window: xcb::ffi::render::xcb_render_pictforminfo_t,
+	argb: Option<xcb::ffi::render::xcb_render_pictforminfo_t>,
+}
+
+impl WindowFormats
+{
+	/// true if windows have an alpha channel and can be translucent
+	fn has_alpha(&self) -> bool
+	{
+		self.window.direct.alpha_mask != 0
+	}
 }
 
-fn pict_formats(c: &xcb::base::Connection)
-	-> (xcb::ffi::render::xcb_render_pictforminfo_t,
-		xcb::ffi::render::xcb_render_pictforminfo_t)
+/// pick the deepest TrueColor visual of the screen that has a render
+/// format, preferring 32 bit ARGB, then 24 and 16 bit RGB
+fn window_formats(c: &xcb::base::Connection, screen_num: i32)
+	-> Result<WindowFormats>
 {
-	type PF = *const xcb::ffi::render::xcb_render_pictforminfo_t;
-	let mut fmt_rgb = None;
-	let mut fmt_rgba = None;
+	let reply = xcb::render::query_pict_formats(c).get_reply()?;
+	let infos : Vec<xcb::ffi::render::xcb_render_pictforminfo_t>
+		= reply.formats().map(|f| f.base).collect();
+
+	let argb = infos.iter()
+		.find(
+			|f|
+				f.type_ == xcb::ffi::render::XCB_RENDER_PICT_TYPE_DIRECT as u8
+				&& f.depth == 32
+				&& f.direct.alpha_mask == 0xff && f.direct.alpha_shift == 24
+				&& f.direct.red_mask == 0xff && f.direct.red_shift == 16
+		)
+		.cloned();
 
-	let formats = xcb::render::query_pict_formats(c).get_reply().unwrap();
-	for f in formats.formats()
+	let screen = c.get_setup().roots().nth(screen_num as usize)
+		.ok_or(Error::NoVisual)?;
+	let mut true_color = vec!();
+	for depth in screen.allowed_depths()
 	{
-		if f.type_() == xcb::ffi::render::XCB_RENDER_PICT_TYPE_DIRECT as u8
+		for visual in depth.visuals()
 		{
-			if f.direct().red_mask() != 0xff &&
-				f.direct().red_shift() != 16
-				{ continue; }
-			if f.depth() == 32
+			if visual.class() == xcb::VISUAL_CLASS_TRUE_COLOR as u8
 			{
-				if f.direct().alpha_mask() == 0xff &&
-					f.direct().alpha_shift() == 24
-				{
-					//eprintln!("PICT {} matches", f.id());
-					fmt_rgba = Some(f.base);
-				}
+				true_color.push((visual.visual_id(), depth.depth()));
 			}
-			if f.depth() == 24
+		}
+	}
+
+	let pict_screen = reply.screens().nth(screen_num as usize)
+		.ok_or(Error::NoPictFormat)?;
+	for &want in &[32u8, 24, 16]
+	{
+		for depth in pict_screen.depths()
+		{
+			if depth.depth() != want { continue; }
+			for visual in depth.visuals()
 			{
-				if fmt_rgb.is_some() { continue; }
-				fmt_rgb = Some(f.base);
+				if !true_color.contains(&(visual.visual(), want)) { continue; }
+				let format = infos.iter().find(
+					|f|
+						f.id == visual.format()
+						&& f.type_ == xcb::ffi::render::XCB_RENDER_PICT_TYPE_DIRECT as u8
+				);
+				if let Some(format) = format
+				{
+					return Ok(WindowFormats
+					{
+						depth: want,
+						visual: visual.visual(),
+						window: *format,
+						argb,
+					});
+				}
 			}
 		}
 	}
-
-	(fmt_rgba.unwrap(), fmt_rgb.unwrap())
+	Err(Error::NoVisual)
 }
 
 
@@ -195,13 +267,71 @@ pub struct GraphicalDetails
 {
 	pub(crate) connection : xcb::base::Connection,
 	pub(crate) screen_num : i32,
-	top_level_widgets: std::vec::Vec<Rc<Widget>>,
-	repaint_everything : Cell<bool>,
+	/// weak, so that dropping a window's widget closes it
+	top_level_widgets: RefCell<std::vec::Vec<Weak<Widget>>>,
+	/// the areas of each window that must be repainted
+	damage : RefCell<Vec<(u32, Vec<Rectangle>)>>,
+	/// while painting, the window position of the widget being
+	/// drawn and the area being repainted
+	paint_origin : Cell<Point>,
+	paint_region : RefCell<Option<Vec<Rectangle>>>,
+	back_buffers : RefCell<Vec<BackBuffer>>,
 	event_post: Arc<queue::EventPoster>,
-	pict_formats: (
-		xcb::ffi::render::xcb_render_pictforminfo_t,
-		xcb::ffi::render::xcb_render_pictforminfo_t
-	),
+	formats: WindowFormats,
+	keymap: RefCell<Keymap>,
+	pending_event: RefCell<Option<xcb::GenericEvent>>,
+	last_key_release: Cell<Option<(u8, u32)>>,
+	focus: RefCell<Vec<(u32, Weak<Widget>)>>,
+	hovered: RefCell<Vec<Weak<Widget>>>,
+	mouse_grab: RefCell<Option<MouseGrab>>,
+	last_click: Cell<Option<Click>>,
+	double_click_interval: Cell<Duration>,
+	drag_threshold: Cell<u32>,
+	timers: Timers,
+	invoker: RefCell<Option<queue::GuiInvoker>>,
+	executor: Executor,
+	exit_code: Cell<Option<i32>>,
+	quit_on_last_window_closed: Cell<bool>,
+	about_to_quit_callbacks: RefCell<Vec<Box<FnMut()>>>,
+	display_lost_callbacks: RefCell<Vec<Box<FnMut(&Error)>>>,
+	deferred: RefCell<Vec<Box<FnOnce()>>>,
+	idle_callbacks: RefCell<Vec<Box<FnMut() -> bool>>>,
+	/// the colormap created for each window
+	colormaps: RefCell<Vec<(u32, u32)>>,
+	/// the WM_PROTOCOLS and WM_DELETE_WINDOW atoms, once interned
+	wm_protocols: Cell<Option<(xcb::Atom, xcb::Atom)>>,
+	animations: Rc<Animations>,
+}
+
+#[derive(Copy, Clone)]
+struct Click
+{
+	button: MouseButton,
+	window: u32,
+	pos: Point,
+	time: u32,
+	count: u32,
+}
+
+/// The off-screen copy of a window that is painted on
+/// and then copied to the window
+struct BackBuffer
+{
+	window: u32,
+	pixmap: u32,
+	gc: u32,
+	size: Size,
+	surface: cairo::surface::Surface,
+}
+
+/// The widget that receives all mouse events, either
+/// while a button is held or until it releases the mouse
+struct MouseGrab
+{
+	widget: Weak<Widget>,
+	/// the position of `widget` in its window
+	origin: Point,
+	implicit: bool,
 }
 
 impl GraphicalDetails
@@ -216,9 +346,10 @@ impl GraphicalDetails
 			.unwrap()
 	}
 	
-	pub(crate) fn make_real_window(&self) -> u32
+	pub(crate) fn make_real_window(&self) -> Result<u32>
 	{
-		let visual = self.get_visual();
+		let (protocols, delete_window) = self.wm_protocols_atoms()?;
+		let visual = self.formats.visual;
 		let screen = self.screen();
 
 		let colormap_id = self.connection.generate_id();
@@ -226,12 +357,12 @@ impl GraphicalDetails
 			&self.connection,
 			xcb::ffi::XCB_COLORMAP_ALLOC_NONE as u8,
 			colormap_id, screen.root(), visual
-		).request_check().map_err(|g| panic!("e = {}", g.error_code())).unwrap();
+		).request_check()?;
 
 		let win = self.connection.generate_id();
 		xcb::create_window_checked(
 			&self.connection,
-			32,
+			self.formats.depth,
 			win,
 			screen.root(),
 			0, 0,
@@ -241,84 +372,212 @@ impl GraphicalDetails
 			visual,
 			&[
 				(xcb::CW_COLORMAP, colormap_id),
-				(xcb::CW_BACK_PIXEL, 0xffc2bbb8),
+				// painting the background is left to us, so
+				// the X server doesn't clear the window first
+				(xcb::CW_BACK_PIXMAP, xcb::BACK_PIXMAP_NONE),
 				(xcb::CW_BORDER_PIXEL, 0),
 				(
 					xcb::CW_EVENT_MASK,
 					xcb::EVENT_MASK_EXPOSURE
 						| xcb::EVENT_MASK_KEY_PRESS
+						| xcb::EVENT_MASK_KEY_RELEASE
 						| xcb::EVENT_MASK_BUTTON_PRESS
 						| xcb::EVENT_MASK_BUTTON_RELEASE
+						| xcb::EVENT_MASK_POINTER_MOTION
+						| xcb::EVENT_MASK_ENTER_WINDOW
+						| xcb::EVENT_MASK_LEAVE_WINDOW
 						| xcb::EVENT_MASK_STRUCTURE_NOTIFY
 				)
 			]
-		).request_check().map_err(|g| panic!("e = {}", g.error_code())).unwrap();
+		).request_check().map_err(
+			|e|
+			{
+				xcb::free_colormap(&self.connection, colormap_id);
+				e
+			}
+		)?;
+		self.colormaps.borrow_mut().push((win, colormap_id));
+
+		// ask the window manager to send a message instead
+		// of killing the connection when the window is closed
+		xcb::change_property(
+			&self.connection,
+			xcb::PROP_MODE_REPLACE as u8,
+			win,
+			protocols,
+			xcb::ATOM_ATOM,
+			32,
+			&[delete_window],
+		);
 		xcb::map_window(&self.connection, win);
 
 		&self.connection.flush();
-		win
+		Ok(win)
 	}
 
-	fn get_visual(&self) -> u32
+	fn wm_protocols_atoms(&self) -> Result<(xcb::Atom, xcb::Atom)>
 	{
-		let screen = self.screen();
-		for depth in screen.allowed_depths()
+		if let Some(atoms) = self.wm_protocols.get()
 		{
-			if depth.depth() == 32
-			{
-				for visual in depth.visuals()
-				{
-					return visual.visual_id();
-				}
-			}
+			return Ok(atoms);
 		}
-		panic!("no visual found");
+		let atoms =
+		(
+			intern_atom(&self.connection, "WM_PROTOCOLS")?,
+			intern_atom(&self.connection, "WM_DELETE_WINDOW")?,
+		);
+		self.wm_protocols.set(Some(atoms));
+		Ok(atoms)
 	}
-	
-	pub fn exec(&self)
+
+	/// destroy a window made by `make_real_window` and the
+	/// resources that belong to it
+	pub(crate) fn destroy_window(&self, window: u32)
+	{
+		if window == 0 { return; }
+		self.free_window_resources(window);
+		xcb::destroy_window(&self.connection, window);
+		self.connection.flush();
+		self.window_closed(window);
+	}
+
+	/// free the colormap and back buffer of `window`
+	fn free_window_resources(&self, window: u32)
+	{
+		let buffer =
+		{
+			let mut buffers = self.back_buffers.borrow_mut();
+			let idx = buffers.iter().position(|b| b.window == window);
+			idx.map(|idx| buffers.remove(idx))
+		};
+		if let Some(b) = buffer
+		{
+			drop(b.surface);
+			xcb::free_pixmap(&self.connection, b.pixmap);
+			xcb::free_gc(&self.connection, b.gc);
+		}
+
+		let colormap =
+		{
+			let mut colormaps = self.colormaps.borrow_mut();
+			let idx = colormaps.iter().position(|&(w, _)| w == window);
+			idx.map(|idx| colormaps.remove(idx).1)
+		};
+		if let Some(c) = colormap
+		{
+			xcb::free_colormap(&self.connection, c);
+		}
+	}
+
+	/// run the event loop until `quit` is called, and return the
+	/// code passed to it, or an error if the display is lost
+	/// or a window can't be painted
+	pub fn exec(&self) -> Result<i32>
 	{
 		//let screen = self.conn.screen();
 
+		let mut failed = None;
 		loop
 		{
-			if self.event_post.wait()
+			if self.exit_code.get().is_some()
+			{
+				break;
+			}
+
+			let mut timeout = self.timers.next_timeout();
+			if !self.deferred.borrow().is_empty() || !self.idle_callbacks.borrow().is_empty()
+			{
+				timeout = Some(Duration::from_secs(0));
+			}
+			// round trips since the events were last read, such as
+			// while painting, may have queued events that poll
+			// won't see on the socket
+			if self.pending_event.borrow().is_none()
+			{
+				*self.pending_event.borrow_mut() = self.connection.poll_for_queued_event();
+			}
+			if self.pending_event.borrow().is_some()
+			{
+				timeout = Some(Duration::from_secs(0));
+			}
+			if self.event_post.wait(timeout)
 			{
 				self.event_post.process_channels();
 			}
+			self.timers.run_due();
+			self.executor.run_ready();
 
-			while let Some(event) = self.connection.poll_for_event()
+			while let Some(event) = self.next_event()
 			{
 				let r = event.response_type() & !0x80;
 				match r
 				{
-					xcb::KEY_PRESS =>
+					xcb::KEY_PRESS | xcb::KEY_RELEASE =>
 					{
-						let key_press : &xcb::KeyPressEvent = unsafe { xcb::cast_event(&event) };
-						println!("Key '{}' pressed", key_press.detail());
+						let key : &xcb::KeyPressEvent = unsafe { xcb::cast_event(&event) };
+						let e = self.key_event(r, key);
+						self.dispatch_key(key.event(), &e);
 					},
-					xcb::BUTTON_PRESS =>
+					xcb::MAPPING_NOTIFY =>
+					{
+						let mapping : &xcb::MappingNotifyEvent
+							= unsafe { xcb::cast_event(&event) };
+						// if the new mapping can't be read,
+						// keep using the old one
+						if mapping.request() == xcb::MAPPING_KEYBOARD as u8
+						{
+							if let Ok(keymap) = Keymap::load(&self.connection)
+							{
+								*self.keymap.borrow_mut() = keymap;
+							}
+						}
+					},
+					xcb::BUTTON_PRESS | xcb::BUTTON_RELEASE =>
 					{
 						let button_press : &xcb::ButtonPressEvent
 							= unsafe { xcb::cast_event(&event) };
 
 						let pos = Point { x: button_press.event_x() as i32, y: button_press.event_y() as i32 };
 
-						for w in &self.top_level_widgets
+						let action = MouseEvent::button_action(
+							r == xcb::BUTTON_PRESS,
+							button_press.detail(),
+						);
+						let action = match action
 						{
-							w.mouse_event(MouseEvent::LeftPress, &pos);
-						}
+							Some(a) => a,
+							None => continue,
+						};
+						let mut e = MouseEvent::new(action, button_press.state(), button_press.time());
+						e.click_count = self.count_click(button_press.event(), &e, &pos);
+						self.dispatch_mouse(button_press.event(), e, pos);
 					},
-					xcb::BUTTON_RELEASE =>
+					xcb::MOTION_NOTIFY =>
 					{
-						let button_press : &xcb::ButtonPressEvent
+						let motion : &xcb::MotionNotifyEvent
 							= unsafe { xcb::cast_event(&event) };
 
-						let pos = Point { x: button_press.event_x() as i32, y: button_press.event_y() as i32 };
+						let pos = Point { x: motion.event_x() as i32, y: motion.event_y() as i32 };
+						let e = MouseEvent::new(MouseAction::Motion, motion.state(), motion.time());
 
-						for w in &self.top_level_widgets
+						if let Some(w) = self.top_level_for_window(motion.event())
 						{
-							w.mouse_event(MouseEvent::LeftRelease, &pos);
+							self.update_hover(Some((&w, pos)));
 						}
+						self.dispatch_mouse(motion.event(), e, pos);
+					},
+					xcb::ENTER_NOTIFY =>
+					{
+						let enter : &xcb::EnterNotifyEvent
+							= unsafe { xcb::cast_event(&event) };
+						let pos = Point { x: enter.event_x() as i32, y: enter.event_y() as i32 };
+
+						let top = self.top_level_for_window(enter.event());
+						self.update_hover(top.as_ref().map(|t| (t, pos)));
+					},
+					xcb::LEAVE_NOTIFY =>
+					{
+						self.update_hover(None);
 					},
 					xcb::CONFIGURE_NOTIFY =>
 					{
@@ -329,235 +588,1326 @@ impl GraphicalDetails
 							width: resize_req.width() as u32,
 							height: resize_req.height() as u32,
 						};
-						for w in &self.top_level_widgets
+						if let Some(w) = self.top_level_for_window(resize_req.window())
+						{
+							// moving the window also sends this, but
+							// exposing already repaints what it uncovers
+							let old = w.rectangle().size;
+							if sz != old
+							{
+								w.as_widget().set_size(sz);
+								w.resized(sz);
+								w.as_widget().notify_resized(old);
+								self.damage(
+									resize_req.window(),
+									Rectangle { pos: Point { x: 0, y: 0 }, size: sz },
+								);
+							}
+						}
+					},
+					xcb::CLIENT_MESSAGE =>
+					{
+						let message : &xcb::ClientMessageEvent
+							= unsafe { xcb::cast_event(&event) };
+						// the atoms are known once a window exists
+						let (protocols, delete_window) = match self.wm_protocols_atoms()
+						{
+							Ok(atoms) => atoms,
+							Err(_) => continue,
+						};
+						if message.type_() == protocols
+							&& message.format() == 32
+							&& message.data().data32()[0] == delete_window
+						{
+							if let Some(w) = self.top_level_for_window(message.window())
+							{
+								if w.close_event()
+								{
+									self.destroy_window(message.window());
+								}
+							}
+						}
+					},
+					xcb::DESTROY_NOTIFY =>
+					{
+						let destroy : &xcb::DestroyNotifyEvent
+							= unsafe { xcb::cast_event(&event) };
+						if destroy.event() == destroy.window()
 						{
-							w.as_widget().set_size(sz);
-							w.resized(sz);
+							self.window_closed(destroy.window());
 						}
 					},
 					xcb::EXPOSE =>
 					{
-						self.paint_everything();
+						let expose : &xcb::ExposeEvent
+							= unsafe { xcb::cast_event(&event) };
+						self.expose(
+							expose.window(),
+							Rectangle::coords(
+								expose.x() as i32, expose.y() as i32,
+								expose.width() as u32, expose.height() as u32,
+							),
+						);
 					},
 					_ => {}
 				}
 			}
-			
-			if self.repaint_everything.get()
+
+			// a broken connection makes the fd always readable
+			// but never returns events
+			if let Err(e) = self.connection.has_error()
 			{
-				self.paint_everything();
+				let e = Error::ConnectionLost(e);
+				for c in self.display_lost_callbacks.borrow_mut().iter_mut()
+					{ c(&e); }
+				failed = Some(e);
+				break;
 			}
-		}
-	}
-	
-	fn paint_everything(&self)
-	{
-		self.repaint_everything.set(false);
-		for w in &self.top_level_widgets
-		{
-			let w = w.as_ref().borrow();
-			let wrect = w.rectangle();
-			let mut surface = surface_from_x(
-				self.connection.get_raw_conn(),
-				self.screen().ptr,
-				&self.pict_formats.0,
-				w.as_widget().true_window_id(),
-				wrect.width() as i32,
-				wrect.height() as i32,
-			);
-			/*unsafe
-			{
-				cairo_xcb_surface_set_size(
-					surface.opaque,
-					wrect.width() as c_int,
-					wrect.height() as c_int,
-				);
-			}*/
 
+			self.run_deferred();
+			self.run_idle();
+			if let Err(e) = self.paint_damage()
 			{
-				use crate::draw::DrawPixel;
-				let mut cr = cairo::Cairo::create(&mut surface);
-				cr.fillcolor(crate::draw::Color::rgb(0xc2,0xbb, 0xb8));
-				w.draw(&mut cr);
+				failed = Some(e);
+				break;
 			}
-			//surface.flush();
-			surface.finish();
 		}
-		
+
+		for c in self.about_to_quit_callbacks.borrow_mut().iter_mut()
+			{ c(); }
 		self.connection.flush();
-	}
 
-	pub fn pixmap(&self, from: &image::DynamicImage)
-		-> cairo::surface::Surface
-	{
-		let mut dest_surface;
-		unsafe
+		// let exec be called again
+		let code = self.exit_code.take().unwrap_or(0);
+		match failed
 		{
-			let dest_id = xcb::ffi::base::xcb_generate_id(self.connection.get_raw_conn());
-
-			xcb::xproto::create_pixmap_checked(
-				&self.connection,
-				32,
-				dest_id,
-				self.screen().root(),
-				from.width() as u16,
-				from.height() as u16,
-			).request_check().map_err(|g| panic!("e = {}", g.error_code())).unwrap();
-			dest_surface = surface_from_x(
-				self.connection.get_raw_conn(),
-				self.screen().ptr,
-				&self.pict_formats.0,
-				dest_id,
-				from.width() as i32,
-				from.height() as i32,
-			);
-
-			let mut source = surface_from_img(from);
-			use crate::draw::DrawPixel;
-			let mut cr = cairo::Cairo::create(&mut dest_surface);
-			cr.set_operator(cairo::operator::Operator::Source);
-			cr.set_source_surface(&mut source, 0.0, 0.0);
-			cr.paint();
+			Some(e) => Err(e),
+			None => Ok(code),
 		}
-		dest_surface
 	}
 
-	fn repaint_everything(&self)
+	/// call `f` on the GUI thread once the current event is handled
+	pub fn invoke_later<F: 'static+FnOnce()>(&self, f: F)
 	{
-		self.repaint_everything.set(true);
+		self.deferred.borrow_mut().push(Box::new(f));
 	}
 
-	fn channel<T: 'static+Clone+Sized+Send, F: 'static+FnMut(T)>(
-		&self,
-		f: F,
-	) -> queue::ChannelWrite<T>
+	/// call `f` whenever the event loop has nothing else to do,
+	/// until it returns false. The loop doesn't sleep while
+	/// there are idle callbacks.
+	pub fn on_idle<F: 'static+FnMut() -> bool>(&self, f: F)
 	{
-		self.event_post.channel(
-			f,
-		)
+		self.idle_callbacks.borrow_mut().push(Box::new(f));
 	}
-}
-
-pub trait Widget
-{
-	fn as_widget(&self) -> &WidgetBase;
-	fn as_widget_mut(&mut self) -> &mut WidgetBase;
 
-	fn det(&self) -> Option<Rc<RefCell<GraphicalDetails>>>
+	fn run_deferred(&self)
 	{
-		self.as_widget()
-			.det
-			.borrow()
-			.clone()
+		// work deferred by these callbacks is run
+		// on the next iteration of the loop
+		let batch = std::mem::replace(&mut *self.deferred.borrow_mut(), vec!());
+		for f in batch
+		{
+			f();
+		}
 	}
 
-	fn name(&self) -> &str { &self.as_widget().name }
-
-	fn trace(&self, depth : usize, o : &mut std::io::Write)
+	fn run_idle(&self)
 	{
-		self.traceme(depth, o);
-	}
-	fn traceme(&self, depth : usize, o : &mut std::io::Write)
-	{
-		writeln!(
-			o,
-			"{}{} {:?}",
-			" ".repeat(depth),
-			self.as_widget().name,
-			self.rectangle(),
-		).unwrap();
+		let current = std::mem::replace(&mut *self.idle_callbacks.borrow_mut(), vec!());
+		let mut current : Vec<_> = current
+			.into_iter()
+			.filter_map(|mut f| if f() { Some(f) } else { None })
+			.collect();
+
+		let mut idle = self.idle_callbacks.borrow_mut();
+		current.extend(idle.drain(..));
+		*idle = current;
 	}
 
-	fn setup(&self, det: Rc<RefCell<GraphicalDetails>>)
+	/// call `cb` when the connection to the X server is lost,
+	/// just before `exec` returns the error
+	pub fn on_display_lost<F: 'static+FnMut(&Error)>(&self, cb: F)
 	{
-		*self.as_widget().det.borrow_mut() = Some(det.clone());
-		self.setup_children(det);
+		self.display_lost_callbacks.borrow_mut().push(Box::new(cb));
 	}
 
-	fn setup_children(&self, _det: Rc<RefCell<GraphicalDetails>>)
+	/// make `exec` return `code` once the current event is handled
+	pub fn quit(&self, code: i32)
 	{
+		if self.exit_code.get().is_none()
+		{
+			self.exit_code.set(Some(code));
+		}
 	}
 
-	fn draw(&self, _ : &mut cairo::Cairo)
+	/// whether to quit with code 0 when the last window is closed, the default
+	pub fn set_quit_on_last_window_closed(&self, quit: bool)
 	{
-		eprintln!("blank painted {}", self.name());
+		self.quit_on_last_window_closed.set(quit);
 	}
-	fn rectangle(&self) -> Rectangle { self.as_widget().rectangle() }
-	fn width(&self) -> u32 { self.as_widget().rectangle().width() }
-	fn height(&self) -> u32 { self.as_widget().rectangle().height() }
-	fn mouse_event(&self, e: MouseEvent, pt: &Point)
+	pub fn quit_on_last_window_closed(&self) -> bool
 	{
-		if let Some(c) = self.child_at(pt)
-		{
-			c.mouse_event(e, &pt);
-		}
+		self.quit_on_last_window_closed.get()
 	}
-	fn resized(&self, _ : Size) { }
-	fn repaint(&self)
+
+	/// call `cb` when `exec` is about to return
+	pub fn on_about_to_quit<F: 'static+FnMut()>(&self, cb: F)
 	{
-		self.as_widget().repaint();
+		self.about_to_quit_callbacks.borrow_mut().push(Box::new(cb));
 	}
 
-	fn minimum_size(&self) -> Size
+	pub(crate) fn add_top_level(&self, w: Rc<Widget>)
 	{
-		//self.as_widget().minimum_size()
-		Size{ width:0, height:0 }
+		self.top_level_widgets.borrow_mut().push(Rc::downgrade(&w));
 	}
-	fn maximum_size(&self) -> Size
+
+	/// the top level widgets that still exist
+	fn top_levels(&self) -> Vec<Rc<Widget>>
 	{
-		self.as_widget().maximum_size()
+		self.top_level_widgets.borrow()
+			.iter()
+			.filter_map(|w| w.upgrade())
+			.collect()
 	}
-	fn size_hint(&self) -> Size
+
+	/// forget the top level widget of `window`, which no longer exists
+	fn window_closed(&self, window: u32)
 	{
-		Size{ width: 100, height: 100 }
+		let mut removed = vec!();
+		let mut dropped = false;
+		{
+			let mut tops = self.top_level_widgets.borrow_mut();
+			tops.retain(
+				|w|
+				{
+					match w.upgrade()
+					{
+						Some(w) if w.as_widget().true_window_id() == window =>
+						{
+							removed.push(w);
+							false
+						},
+						Some(_) => true,
+						// a widget being dropped closes its window
+						None => { dropped = true; false },
+					}
+				}
+			);
+		}
+		if removed.is_empty() && !dropped { return; }
+
+		// the window is gone, so dropping the widgets
+		// must not destroy it again
+		for w in &removed
+		{
+			w.as_widget().true_window_id.set(0);
+		}
+		drop(removed);
+		self.free_window_resources(window);
+
+		self.focus.borrow_mut().retain(|&(w, _)| w != window);
+		self.damage.borrow_mut().retain(|&(w, _)| w != window);
+
+		let no_windows = self.top_levels()
+			.iter()
+			.all(|w| w.as_widget().true_window_id() == 0);
+		if no_windows && self.quit_on_last_window_closed.get()
+		{
+			self.quit(0);
+		}
 	}
 	
-	fn size_policy(&self) -> SizePolicy
-	{
-		SizePolicy{ vertical: Policy::Expanding, horizontal: Policy::Expanding }
-	}
-	fn is_visible(&self) -> bool
+	/// the top level widget whose X window is `window`
+	fn top_level_for_window(&self, window: u32) -> Option<Rc<Widget>>
 	{
-		true
+		self.top_levels()
+			.into_iter()
+			.find(|w| w.as_widget().true_window_id() == window)
 	}
 
-	fn set_position(&self, pos: &Point)
+	fn next_event(&self) -> Option<xcb::GenericEvent>
 	{
-		let mut rect = self.as_widget().rectangle.get();
-		rect.set_position(pos);
-		self.as_widget().rectangle.set(rect);
+		if let Some(e) = self.pending_event.borrow_mut().take()
+		{
+			return Some(e);
+		}
+		self.connection.poll_for_event()
 	}
 
-	fn resize(&self, sz: &Size)
-	{
-		let mut rect = self.as_widget().rectangle.get();
-		rect.resize(sz);
-		self.as_widget().rectangle.set(rect);
-		self.resized(*sz);
-	}
-	fn set_geometry(&self, rect : Rectangle)
-	{
-		self.as_widget().rectangle.set(rect);
-		self.resized(rect.size);
-	}
-	fn child_at(&self, _pt: &Point) -> Option<Rc<Widget>>
+	fn key_event(&self, response_type: u8, key: &xcb::KeyPressEvent) -> KeyEvent
 	{
-		None
+		let modifiers = Modifiers::from_x_state(key.state());
+		let keysym = self.keymap.borrow().keysym(key.detail(), &modifiers);
+
+		let action;
+		let autorepeat;
+		if response_type == xcb::KEY_RELEASE
+		{
+			action = KeyAction::Release;
+			self.last_key_release.set(Some((key.detail(), key.time())));
+
+			// X reports a held key as a release immediately followed
+			// by a press with the same timestamp
+			let next = self.connection.poll_for_queued_event();
+			autorepeat = match next
+			{
+				Some(ref next) if next.response_type() & !0x80 == xcb::KEY_PRESS =>
+				{
+					let next : &xcb::KeyPressEvent = unsafe { xcb::cast_event(next) };
+					next.detail() == key.detail() && next.time() == key.time()
+				},
+				_ => false,
+			};
+			*self.pending_event.borrow_mut() = next;
+		}
+		else
+		{
+			action = KeyAction::Press;
+			autorepeat = self.last_key_release.get() == Some((key.detail(), key.time()));
+		}
+
+		KeyEvent
+		{
+			action,
+			keysym,
+			keycode: key.detail(),
+			text: keysym_to_text(keysym),
+			modifiers,
+			autorepeat,
+			time: key.time(),
+		}
 	}
 
-	fn pt_from_parent(&self, mut pt: Point) -> Point
+	fn dispatch_key(&self, window: u32, e: &KeyEvent)
 	{
-		pt.x -= self.rectangle().x();
-		pt.y -= self.rectangle().y();
-		pt
+		let top = match self.top_level_for_window(window)
+		{
+			Some(top) => top,
+			None => return,
+		};
+
+		let target = self.focus_widget(window).unwrap_or_else(|| top.clone());
+		if target.key_event(e) { return; }
+
+		if e.is_press() && (e.keysym == keysym::TAB || e.keysym == keysym::ISO_LEFT_TAB)
+		{
+			let backwards = e.modifiers.shift || e.keysym == keysym::ISO_LEFT_TAB;
+			self.move_focus(&top, !backwards);
+		}
 	}
-	fn rect_from_parent(&self, mut r: Rectangle) -> Rectangle
+
+	/// the widget that receives key events in `window`
+	pub fn focus_widget(&self, window: u32) -> Option<Rc<Widget>>
 	{
-		r.pos.x -= self.rectangle().x();
-		r.pos.y -= self.rectangle().y();
-		r
+		self.focus
+			.borrow()
+			.iter()
+			.find(|&&(w, _)| w == window)
+			.and_then(|&(_, ref f)| f.upgrade())
 	}
-}
+
+	fn set_focus(&self, window: u32, new: Option<Rc<Widget>>)
+	{
+		let old = self.focus_widget(window);
+		if let (Some(old), Some(new)) = (&old, &new)
+		{
+			if same_widget(&**old, &**new) { return; }
+		}
+
+		{
+			let mut focus = self.focus.borrow_mut();
+			focus.retain(|&(w, _)| w != window);
+			if let Some(ref new) = new
+			{
+				focus.push((window, Rc::downgrade(new)));
+			}
+		}
+
+		if let Some(old) = old
+		{
+			old.as_widget().has_focus.set(false);
+			old.focus_out();
+		}
+		if let Some(new) = new
+		{
+			new.as_widget().has_focus.set(true);
+			new.focus_in();
+		}
+	}
+
+	pub(crate) fn focus(&self, w: &Rc<Widget>)
+	{
+		if let Some((top, _)) = self.locate(w.as_widget())
+		{
+			self.set_focus(top.as_widget().true_window_id(), Some(w.clone()));
+		}
+	}
+
+	/// the top level widget that contains `w`,
+	/// and the position of `w` in its window
+	fn locate(&self, w: &WidgetBase) -> Option<(Rc<Widget>, Point)>
+	{
+		if let Some(found) = to_window(w, Point { x: 0, y: 0 })
+		{
+			return Some(found);
+		}
+
+		// the parents of `w` aren't all known, so search
+		// down from the windows instead
+		for top in &self.top_levels()
+		{
+			if let Some(origin) = origin_in(top, w, Point { x: 0, y: 0 })
+			{
+				return Some((top.clone(), origin));
+			}
+		}
+		None
+	}
+
+	fn move_focus(&self, top: &Rc<Widget>, forward: bool)
+	{
+		let mut chain = vec!();
+		focus_chain(top, &mut chain);
+		if chain.is_empty() { return; }
+
+		let window = top.as_widget().true_window_id();
+		let current = self.focus_widget(window)
+			.and_then(|f| chain.iter().position(|c| same_widget(&**c, &*f)));
+
+		let next = match current
+		{
+			Some(i) if forward => (i+1) % chain.len(),
+			Some(i) => (i + chain.len() - 1) % chain.len(),
+			None if forward => 0,
+			None => chain.len()-1,
+		};
+		self.set_focus(window, Some(chain[next].clone()));
+	}
+
+	fn click_focus(&self, top: &Rc<Widget>, pos: &Point)
+	{
+		let target = top.path_at(pos)
+			.into_iter()
+			.rev()
+			.map(|(w, _)| w)
+			.find(|w| w.accepts_focus());
+		if let Some(target) = target
+		{
+			self.set_focus(top.as_widget().true_window_id(), Some(target));
+		}
+	}
+
+	/// how many times in a row the button of `e` has been
+	/// clicked at about the same place
+	fn count_click(&self, window: u32, e: &MouseEvent, pos: &Point) -> u32
+	{
+		let last = self.last_click.get();
+		match e.action
+		{
+			MouseAction::Press(button) =>
+			{
+				let interval = self.double_click_interval.get().as_millis() as u32;
+
+				let count = match last
+				{
+					Some(l) if l.button == button && l.window == window
+						&& e.time.wrapping_sub(l.time) <= interval
+						&& !self.exceeds_drag_threshold(&l.pos, pos)
+						=> l.count + 1,
+					_ => 1,
+				};
+				self.last_click.set(
+					Some(Click { button, window, pos: *pos, time: e.time, count })
+				);
+				count
+			},
+			MouseAction::Release(button) =>
+			{
+				match last
+				{
+					Some(l) if l.button == button => l.count,
+					_ => 1,
+				}
+			},
+			_ => 0,
+		}
+	}
+
+	/// the longest time between two clicks of a double click
+	pub fn double_click_interval(&self) -> Duration
+	{
+		self.double_click_interval.get()
+	}
+	pub fn set_double_click_interval(&self, interval: Duration)
+	{
+		self.double_click_interval.set(interval);
+	}
+
+	/// how far, in pixels, the pointer must move
+	/// with a button held before it's a drag
+	pub fn drag_threshold(&self) -> u32
+	{
+		self.drag_threshold.get()
+	}
+	pub fn set_drag_threshold(&self, pixels: u32)
+	{
+		self.drag_threshold.set(pixels);
+	}
+
+	/// call `f` once on the GUI thread after `delay`
+	pub fn single_shot<F: 'static+FnOnce()>(&self, delay: Duration, f: F) -> Timer
+	{
+		let mut f = Some(f);
+		self.timers.add(
+			delay,
+			None,
+			Box::new(move || if let Some(f) = f.take() { f() }),
+		)
+	}
+	/// call `f` on the GUI thread every `interval` until the timer is cancelled
+	pub fn interval<F: 'static+FnMut()>(&self, interval: Duration, f: F) -> Timer
+	{
+		self.timers.add(interval, Some(interval), Box::new(f))
+	}
+
+	/// call `f` on the GUI thread whenever `fd` is ready for `interest`.
+	/// Watches are level triggered, so `f` must read or write
+	/// until the fd would block, or it is called again at once.
+	pub fn watch_fd<F: 'static+FnMut(queue::FdReady)>(
+		&self,
+		fd: std::os::unix::io::RawFd,
+		interest: queue::FdInterest,
+		f: F,
+	) -> queue::FdWatch
+	{
+		self.event_post.watch_fd(fd, interest, Box::new(f))
+	}
+
+	/// run `future` on the GUI thread; it is polled by the
+	/// event loop whenever it is woken
+	pub fn spawn_local<F: 'static+std::future::Future<Output=()>>(&self, future: F)
+	{
+		self.executor.spawn(Box::pin(future));
+	}
+	/// a future that completes after `delay`
+	pub fn sleep(&self, delay: Duration) -> Sleep
+	{
+		Sleep::new(&self.timers, delay)
+	}
+
+	/// run `anim` from its start, stepping it every frame
+	pub fn animate(&self, anim: Rc<Animate>) -> AnimationHandle
+	{
+		Animations::start(&self.animations, &self.timers, anim)
+	}
+
+	/// false if the display has no 32 bit visual, so
+	/// windows are opaque whatever is painted on them
+	pub fn has_translucent_windows(&self) -> bool
+	{
+		self.formats.has_alpha()
+	}
+
+	/// true if moving from `from` to `to` is far enough to start a drag
+	pub fn exceeds_drag_threshold(&self, from: &Point, to: &Point) -> bool
+	{
+		let dx = (to.x - from.x).abs() as u32;
+		let dy = (to.y - from.y).abs() as u32;
+		dx.max(dy) >= self.drag_threshold.get()
+	}
+
+	fn dispatch_mouse(&self, window: u32, e: MouseEvent, pos: Point)
+	{
+		let grabbed = self.mouse_grab
+			.borrow()
+			.as_ref()
+			.and_then(|g| g.widget.upgrade().map(|w| (w, g.origin)));
+
+		if let Some((w, origin)) = grabbed
+		{
+			w.mouse_event(e, &Point { x: pos.x - origin.x, y: pos.y - origin.y });
+
+			if let MouseAction::Release(b) = e.action
+			{
+				let mut grab = self.mouse_grab.borrow_mut();
+				let implicit = grab.as_ref().map(|g| g.implicit).unwrap_or(false);
+				if implicit && !e.buttons.without(b).any()
+				{
+					*grab = None;
+				}
+			}
+			return;
+		}
+		self.mouse_grab.replace(None);
+
+		let w = match self.top_level_for_window(window)
+		{
+			Some(w) => w,
+			None => return,
+		};
+
+		if let MouseAction::Press(_) = e.action
+		{
+			self.click_focus(&w, &pos);
+
+			let (target, local) = w.path_at(&pos).pop().unwrap();
+			self.mouse_grab.replace(
+				Some(MouseGrab
+				{
+					widget: Rc::downgrade(&target),
+					origin: Point { x: pos.x - local.x, y: pos.y - local.y },
+					implicit: true,
+				})
+			);
+		}
+		w.mouse_event(e, &pos);
+	}
+
+	pub(crate) fn grab_mouse(&self, w: &Rc<Widget>)
+	{
+		let (top, origin) = match self.locate(w.as_widget())
+		{
+			Some(l) => l,
+			None => return,
+		};
+
+		self.mouse_grab.replace(
+			Some(MouseGrab
+			{
+				widget: Rc::downgrade(w),
+				origin,
+				implicit: false,
+			})
+		);
+
+		// keep getting pointer events once it leaves the window
+		xcb::grab_pointer(
+			&self.connection,
+			false,
+			top.as_widget().true_window_id(),
+			(xcb::EVENT_MASK_BUTTON_PRESS
+				| xcb::EVENT_MASK_BUTTON_RELEASE
+				| xcb::EVENT_MASK_POINTER_MOTION) as u16,
+			xcb::GRAB_MODE_ASYNC as u8,
+			xcb::GRAB_MODE_ASYNC as u8,
+			xcb::NONE,
+			xcb::NONE,
+			xcb::CURRENT_TIME,
+		);
+		self.connection.flush();
+	}
+
+	pub(crate) fn release_mouse(&self, w: &WidgetBase)
+	{
+		let mut grab = self.mouse_grab.borrow_mut();
+		let release = match *grab
+		{
+			Some(ref g) => g.widget.upgrade().map(|g| std::ptr::eq(g.as_widget(), w)).unwrap_or(true),
+			None => false,
+		};
+		if release
+		{
+			if !grab.as_ref().unwrap().implicit
+			{
+				xcb::ungrab_pointer(&self.connection, xcb::CURRENT_TIME);
+				self.connection.flush();
+			}
+			*grab = None;
+		}
+	}
+
+	pub(crate) fn has_mouse_grab(&self, w: &WidgetBase) -> bool
+	{
+		self.mouse_grab
+			.borrow()
+			.as_ref()
+			.and_then(|g| g.widget.upgrade())
+			.map(|g| std::ptr::eq(g.as_widget(), w))
+			.unwrap_or(false)
+	}
+
+	/// send enter and leave events to the widgets that are
+	/// no longer or newly under the pointer, which is now at
+	/// the given position in the window of a top level widget
+	fn update_hover(&self, under: Option<(&Rc<Widget>, Point)>)
+	{
+		let now : Vec<Rc<Widget>> = match under
+		{
+			Some((top, pos)) => top.path_at(&pos).into_iter().map(|(w, _)| w).collect(),
+			None => vec!(),
+		};
+		let before : Vec<Rc<Widget>> = self.hovered
+			.borrow()
+			.iter()
+			.filter_map(|w| w.upgrade())
+			.collect();
+
+		*self.hovered.borrow_mut() = now.iter().map(|w| Rc::downgrade(w)).collect();
+
+		let contains = |list: &[Rc<Widget>], w: &Rc<Widget>|
+			list.iter().any(|o| same_widget(&**o, &**w));
+
+		for w in before.iter().rev()
+		{
+			if !contains(&now, w)
+			{
+				w.as_widget().hovered.set(false);
+				w.leave_event();
+			}
+		}
+		for w in &now
+		{
+			if !contains(&before, w)
+			{
+				w.as_widget().hovered.set(true);
+				w.enter_event();
+			}
+		}
+	}
+
+	/// add `rect`, in window coordinates, to the area
+	/// of `window` that will be repainted
+	fn damage(&self, window: u32, rect: Rectangle)
+	{
+		if rect.is_empty() { return; }
+
+		let mut damage = self.damage.borrow_mut();
+		let idx = match damage.iter().position(|&(w, _)| w == window)
+		{
+			Some(idx) => idx,
+			None =>
+			{
+				damage.push((window, vec!()));
+				damage.len()-1
+			},
+		};
+		add_damage(&mut damage[idx].1, rect);
+	}
+
+	/// mark the area `rect` of `w`, in its own coordinates, to be repainted
+	pub(crate) fn damage_widget(&self, w: &WidgetBase, rect: Rectangle)
+	{
+		if let Some((top, origin)) = self.locate(w)
+		{
+			let window_rect = Rectangle::coords(0, 0, top.width(), top.height());
+			if let Some(rect) = rect.translated(&origin).intersection(&window_rect)
+			{
+				self.damage(top.as_widget().true_window_id(), rect);
+			}
+		}
+	}
+
+	fn paint_damage(&self) -> Result<()>
+	{
+		let damage : Vec<_> = self.damage.borrow_mut().drain(..).collect();
+		if damage.is_empty() { return Ok(()); }
+
+		let mut result = Ok(());
+		for (window, region) in damage
+		{
+			if let Some(w) = self.top_level_for_window(window)
+			{
+				let r = self.paint_window(&w, region);
+				if result.is_ok() { result = r; }
+			}
+		}
+
+		self.connection.flush();
+		result
+	}
+
+	/// draw `child` at its position, clipped to its rectangle,
+	/// unless it's entirely outside of the area being repainted
+	pub(crate) fn draw_child(&self, c: &mut cairo::Cairo, child: &Widget)
+	{
+		let wrect = child.rectangle();
+		let origin = self.paint_origin.get();
+
+		if let Some(ref region) = *self.paint_region.borrow()
+		{
+			let on_window = wrect.translated(&origin);
+			if !region.iter().any(|r| r.intersects(&on_window))
+			{
+				return;
+			}
+		}
+
+		self.paint_origin.set(Point { x: origin.x + wrect.x(), y: origin.y + wrect.y() });
+		c.save();
+		c.translate(wrect.x() as f64, wrect.y() as f64);
+		c.rectangle(0.0, 0.0, wrect.width() as f64, wrect.height() as f64);
+		c.clip();
+		child.draw(c);
+		c.restore();
+		self.paint_origin.set(origin);
+	}
+
+	/// show the area `rect` of `window` again, from the back
+	/// buffer if it's still current or else by repainting it
+	fn expose(&self, window: u32, rect: Rectangle)
+	{
+		let size = match self.top_level_for_window(window)
+		{
+			Some(w) => w.rectangle().size,
+			None => return,
+		};
+
+		{
+			let buffers = self.back_buffers.borrow();
+			if let Some(b) = buffers.iter().find(|b| b.window == window && b.size == size)
+			{
+				self.copy_to_window(b, &rect);
+				self.connection.flush();
+				return;
+			}
+		}
+		self.damage(window, rect);
+	}
+
+	fn copy_to_window(&self, buffer: &BackBuffer, r: &Rectangle)
+	{
+		xcb::copy_area(
+			&self.connection,
+			buffer.pixmap,
+			buffer.window,
+			buffer.gc,
+			r.x() as i16, r.y() as i16,
+			r.x() as i16, r.y() as i16,
+			r.width() as u16, r.height() as u16,
+		);
+	}
+
+	/// the back buffer of `window`, made anew if the
+	/// window is no longer the size of the old one
+	fn take_back_buffer(&self, window: u32, size: Size) -> Result<BackBuffer>
+	{
+		let old =
+		{
+			let mut buffers = self.back_buffers.borrow_mut();
+			let idx = buffers.iter().position(|b| b.window == window);
+			idx.map(|idx| buffers.remove(idx))
+		};
+
+		let gc = match old
+		{
+			Some(b) =>
+			{
+				if b.size == size
+				{
+					return Ok(b);
+				}
+				let gc = b.gc;
+				let pixmap = b.pixmap;
+				drop(b.surface);
+				xcb::free_pixmap(&self.connection, pixmap);
+				gc
+			},
+			None =>
+			{
+				let gc = self.connection.generate_id();
+				xcb::create_gc(
+					&self.connection,
+					gc,
+					window,
+					&[(xcb::GC_GRAPHICS_EXPOSURES, 0)],
+				);
+				gc
+			},
+		};
+
+		let pixmap = self.connection.generate_id();
+		xcb::create_pixmap(
+			&self.connection,
+			self.formats.depth,
+			pixmap,
+			window,
+			size.width as u16,
+			size.height as u16,
+		);
+		let surface = surface_from_x(
+			self.connection.get_raw_conn(),
+			self.screen().ptr,
+			&self.formats.window,
+			pixmap,
+			size.width as i32,
+			size.height as i32,
+		);
+		match surface
+		{
+			Ok(surface) => Ok(BackBuffer { window, pixmap, gc, size, surface }),
+			Err(e) =>
+			{
+				xcb::free_pixmap(&self.connection, pixmap);
+				xcb::free_gc(&self.connection, gc);
+				Err(e)
+			},
+		}
+	}
+
+	fn paint_window(&self, w: &Rc<Widget>, region: Vec<Rectangle>) -> Result<()>
+	{
+		let w = w.as_ref().borrow();
+		let wrect = w.rectangle();
+		if wrect.is_empty() { return Ok(()); }
+
+		let window = w.as_widget().true_window_id();
+		let mut buffer = self.take_back_buffer(window, wrect.size)?;
+
+		{
+			use crate::draw::ColorSetter;
+			let mut cr = cairo::Cairo::create(&mut buffer.surface);
+			for r in &region
+			{
+				cr.rectangle(r.x() as f64, r.y() as f64, r.width() as f64, r.height() as f64);
+			}
+			cr.clip();
+			cr.set_color(crate::draw::Color::rgb(0xc2,0xbb, 0xb8));
+			cr.paint();
+
+			self.paint_origin.set(Point { x: 0, y: 0 });
+			*self.paint_region.borrow_mut() = Some(region);
+			w.draw(&mut cr);
+		}
+		buffer.surface.flush();
+
+		let region = self.paint_region.borrow_mut().take().unwrap();
+		for r in &region
+		{
+			self.copy_to_window(&buffer, r);
+		}
+		self.back_buffers.borrow_mut().push(buffer);
+		Ok(())
+	}
+
+	pub fn pixmap(&self, from: &image::DynamicImage)
+		-> cairo::surface::Surface
+	{
+		self.try_pixmap(from).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// copy `from` to a new pixmap on the X server
+	pub fn try_pixmap(&self, from: &image::DynamicImage)
+		-> Result<cairo::surface::Surface>
+	{
+		let mut dest_surface;
+		unsafe
+		{
+			if let Some(ref argb) = self.formats.argb
+			{
+				let dest_id = xcb::ffi::base::xcb_generate_id(self.connection.get_raw_conn());
+
+				xcb::xproto::create_pixmap_checked(
+					&self.connection,
+					32,
+					dest_id,
+					self.screen().root(),
+					from.width() as u16,
+					from.height() as u16,
+				).request_check()?;
+				dest_surface = surface_from_x(
+					self.connection.get_raw_conn(),
+					self.screen().ptr,
+					argb,
+					dest_id,
+					from.width() as i32,
+					from.height() as i32,
+				)?;
+			}
+			else
+			{
+				// the server can't hold images with an alpha
+				// channel, so keep them in our memory
+				dest_surface = image_surface(from.width() as i32, from.height() as i32)?;
+			}
+
+			let mut source = surface_from_img(from)?;
+			use crate::draw::DrawPixel;
+			let mut cr = cairo::Cairo::create(&mut dest_surface);
+			cr.set_operator(cairo::operator::Operator::Source);
+			cr.set_source_surface(&mut source, 0.0, 0.0);
+			cr.paint();
+		}
+		Ok(dest_surface)
+	}
+
+
+	fn channel<T: 'static+Sized+Send, F: 'static+FnMut(T) -> R, R: queue::HandlerResult>(
+		&self,
+		f: F,
+	) -> queue::ChannelWrite<T>
+	{
+		self.event_post.channel(
+			f,
+		)
+	}
+
+	/// a handle for running closures on this thread from other threads
+	pub fn invoker(&self) -> queue::GuiInvoker
+	{
+		self.invoker
+			.borrow_mut()
+			.get_or_insert_with(|| queue::GuiInvoker::new(&self.event_post))
+			.clone()
+	}
+
+	fn bounded_channel<T: 'static+Sized+Send, F: 'static+FnMut(T) -> R, R: queue::HandlerResult>(
+		&self,
+		capacity: usize,
+		f: F,
+	) -> queue::ChannelWrite<T>
+	{
+		self.event_post.bounded_channel(
+			capacity,
+			f,
+		)
+	}
+}
+
+pub trait Widget
+{
+	fn as_widget(&self) -> &WidgetBase;
+	fn as_widget_mut(&mut self) -> &mut WidgetBase;
+
+	fn det(&self) -> Option<Rc<RefCell<GraphicalDetails>>>
+	{
+		self.as_widget()
+			.det
+			.borrow()
+			.clone()
+	}
+
+	fn name(&self) -> &str { &self.as_widget().name }
+
+	fn trace(&self, depth : usize, o : &mut std::io::Write)
+	{
+		self.traceme(depth, o);
+	}
+	fn traceme(&self, depth : usize, o : &mut std::io::Write)
+	{
+		writeln!(
+			o,
+			"{}{} {:?}",
+			" ".repeat(depth),
+			self.as_widget().name,
+			self.rectangle(),
+		).unwrap();
+	}
+
+	fn setup(&self, det: Rc<RefCell<GraphicalDetails>>)
+	{
+		*self.as_widget().det.borrow_mut() = Some(det.clone());
+		self.setup_children(det);
+	}
+
+	/// like `setup`, for widgets whose setup can fail,
+	/// such as those that make a window
+	fn try_setup(&self, det: Rc<RefCell<GraphicalDetails>>) -> Result<()>
+	{
+		self.setup(det);
+		Ok(())
+	}
+
+	fn setup_children(&self, det: Rc<RefCell<GraphicalDetails>>)
+	{
+		for c in self.children()
+		{
+			// containers that only provide their children
+			// don't register them or set their parent
+			register_child(&c);
+			set_parent(c.as_widget(), self.as_widget());
+			c.setup(det.clone());
+		}
+	}
+
+	/// every widget directly contained by this one
+	fn children(&self) -> Vec<Rc<Widget>>
+	{
+		vec!()
+	}
+	/// the widget that contains this one, if any
+	fn parent(&self) -> Option<Rc<Widget>>
+	{
+		self.as_widget().parent()
+	}
+	/// emitted with the new size when this widget's size changes
+	fn size_changed(&self) -> &Signal<Size>
+	{
+		self.as_widget().size_changed()
+	}
+	/// the top level widget with an X window that contains
+	/// this widget, which may be this widget itself
+	fn window(&self) -> Option<Rc<Widget>>
+	{
+		to_window(self.as_widget(), Point { x: 0, y: 0 }).map(|(w, _)| w)
+	}
+	/// the first widget named `name` among this widget's
+	/// descendants, searching depth first
+	fn find_child_by_name(&self, name: &str) -> Option<Rc<Widget>>
+	{
+		for c in self.children()
+		{
+			if c.name() == name
+				{ return Some(c); }
+			if let Some(found) = c.find_child_by_name(name)
+				{ return Some(found); }
+		}
+		None
+	}
+	/// the children currently shown, in drawing and focus order
+	fn visible_children(&self) -> Vec<Rc<Widget>>
+	{
+		self.children()
+			.into_iter()
+			.filter(|c| c.is_visible())
+			.collect()
+	}
+
+	/// paint this widget; by default, paint its visible children
+	fn draw(&self, c: &mut cairo::Cairo)
+	{
+		draw_children(c, self);
+	}
+	fn rectangle(&self) -> Rectangle { self.as_widget().rectangle() }
+	fn width(&self) -> u32 { self.as_widget().rectangle().width() }
+	fn height(&self) -> u32 { self.as_widget().rectangle().height() }
+	/// called with `pt` in this widget's coordinates; by default,
+	/// forward the event to the child under `pt`
+	fn mouse_event(&self, e: MouseEvent, pt: &Point)
+	{
+		if let Some(c) = self.child_at(pt)
+		{
+			c.mouse_event(e, &c.map_from_parent(*pt));
+		}
+	}
+	/// the window manager asks to close this top level widget's
+	/// window; return false to keep it open
+	fn close_event(&self) -> bool
+	{
+		true
+	}
+
+	/// called for key presses and releases, returns true
+	/// if the event was used
+	fn key_event(&self, _e: &KeyEvent) -> bool
+	{
+		false
+	}
+	fn accepts_focus(&self) -> bool
+	{
+		self.as_widget().focusable.get()
+	}
+	fn set_focusable(&self, focusable: bool)
+	{
+		self.as_widget().focusable.set(focusable);
+	}
+	fn has_focus(&self) -> bool
+	{
+		self.as_widget().has_focus.get()
+	}
+	/// make this widget receive the key events of its window
+	fn set_focus(&self)
+	{
+		if let (Some(det), Some(me)) = (self.det(), self.as_widget().this())
+		{
+			let det : &RefCell<GraphicalDetails> = det.borrow();
+			det.borrow().focus(&me);
+		}
+	}
+	fn focus_in(&self)
+	{
+		self.repaint();
+	}
+	fn focus_out(&self)
+	{
+		self.repaint();
+	}
+
+	/// send all mouse events to this widget, even those outside
+	/// of it, until `release_mouse` is called
+	fn grab_mouse(&self)
+	{
+		if let (Some(det), Some(me)) = (self.det(), self.as_widget().this())
+		{
+			let det : &RefCell<GraphicalDetails> = det.borrow();
+			det.borrow().grab_mouse(&me);
+		}
+	}
+	fn release_mouse(&self)
+	{
+		if let Some(det) = self.det()
+		{
+			let det : &RefCell<GraphicalDetails> = det.borrow();
+			det.borrow().release_mouse(self.as_widget());
+		}
+	}
+	/// true if the pointer moved far enough from
+	/// `from` to `to` to be dragging rather than clicking
+	fn exceeds_drag_threshold(&self, from: &Point, to: &Point) -> bool
+	{
+		match self.det()
+		{
+			Some(det) =>
+			{
+				let det : &RefCell<GraphicalDetails> = det.borrow();
+				let exceeds = det.borrow().exceeds_drag_threshold(from, to);
+				exceeds
+			},
+			None => false,
+		}
+	}
+
+	fn has_mouse_grab(&self) -> bool
+	{
+		match self.det()
+		{
+			Some(det) =>
+			{
+				let det : &RefCell<GraphicalDetails> = det.borrow();
+				let has = det.borrow().has_mouse_grab(self.as_widget());
+				has
+			},
+			None => false,
+		}
+	}
+
+	/// true while the pointer is over this widget or one of its children
+	fn is_hovered(&self) -> bool
+	{
+		self.as_widget().hovered.get()
+	}
+	/// called when the pointer moves onto this widget
+	fn enter_event(&self) { }
+	/// called when the pointer moves off of this widget
+	fn leave_event(&self) { }
+
+	fn resized(&self, _ : Size) { }
+	/// repaint all of this widget once the current events are handled
+	fn update(&self)
+	{
+		let r = self.rectangle();
+		self.repaint_rect(Rectangle::coords(0, 0, r.width(), r.height()));
+	}
+	/// repaint the area `rect`, in this widget's coordinates
+	fn repaint_rect(&self, rect: Rectangle)
+	{
+		self.as_widget().repaint(rect);
+	}
+	fn repaint(&self)
+	{
+		self.update();
+	}
+
+	fn minimum_size(&self) -> Size
+	{
+		//self.as_widget().minimum_size()
+		Size{ width:0, height:0 }
+	}
+	fn maximum_size(&self) -> Size
+	{
+		self.as_widget().maximum_size()
+	}
+	fn size_hint(&self) -> Size
+	{
+		Size{ width: 100, height: 100 }
+	}
+	
+	fn size_policy(&self) -> SizePolicy
+	{
+		SizePolicy{ vertical: Policy::Expanding, horizontal: Policy::Expanding }
+	}
+	fn is_visible(&self) -> bool
+	{
+		true
+	}
+
+	fn set_position(&self, pos: &Point)
+	{
+		let mut rect = self.as_widget().rectangle.get();
+		rect.set_position(pos);
+		self.as_widget().rectangle.set(rect);
+	}
+
+	fn resize(&self, sz: &Size)
+	{
+		let mut rect = self.as_widget().rectangle.get();
+		let old = rect.size;
+		rect.resize(sz);
+		self.as_widget().rectangle.set(rect);
+		self.resized(*sz);
+		self.as_widget().notify_resized(old);
+	}
+	fn set_geometry(&self, rect : Rectangle)
+	{
+		let old = self.as_widget().rectangle.get().size;
+		self.as_widget().rectangle.set(rect);
+		self.resized(rect.size);
+		self.as_widget().notify_resized(old);
+	}
+	/// the visible child under `pt`, the last drawn if they overlap
+	fn child_at(&self, pt: &Point) -> Option<Rc<Widget>>
+	{
+		self.visible_children()
+			.into_iter()
+			.rev()
+			.find(|c| c.rectangle().contains(pt))
+	}
+
+	/// `pt` in this widget's coordinates, mapped to its parent's
+	fn map_to_parent(&self, mut pt: Point) -> Point
+	{
+		pt.x += self.rectangle().x();
+		pt.y += self.rectangle().y();
+		pt
+	}
+	/// `pt` in the parent's coordinates, mapped to this widget's
+	fn map_from_parent(&self, pt: Point) -> Point
+	{
+		self.rectangle().map_to(&pt)
+	}
+	/// `pt` mapped to the coordinates of the window this
+	/// widget is in, or `None` if it isn't in one
+	fn map_to_window(&self, pt: Point) -> Option<Point>
+	{
+		to_window(self.as_widget(), pt).map(|(_, pt)| pt)
+	}
+	/// `pt` mapped to the coordinates of the screen, asking
+	/// the X server where this widget's window is
+	fn map_to_global(&self, pt: Point) -> Option<Point>
+	{
+		let (window, pt) = to_window(self.as_widget(), pt)?;
+		let window = window.as_widget().true_window_id();
+		let det = self.det()?;
+		let det : &RefCell<GraphicalDetails> = det.borrow();
+		let det = det.borrow();
+
+		let r = xcb::translate_coordinates(
+			&det.connection,
+			window,
+			det.screen().root(),
+			pt.x as i16,
+			pt.y as i16,
+		).get_reply().ok()?;
+		Some(Point { x: r.dst_x() as i32, y: r.dst_y() as i32 })
+	}
+
+	/// `r` in the parent's coordinates, mapped to this widget's
+	fn map_rect_from_parent(&self, mut r: Rectangle) -> Rectangle
+	{
+		r.pos = self.map_from_parent(r.pos);
+		r
+	}
+
+	#[deprecated(note = "use `map_from_parent`")]
+	fn pt_from_parent(&self, pt: Point) -> Point
+	{
+		self.map_from_parent(pt)
+	}
+	#[deprecated(note = "use `map_rect_from_parent`")]
+	fn rect_from_parent(&self, r: Rectangle) -> Rectangle
+	{
+		self.map_rect_from_parent(r)
+	}
+}
 
 impl std::fmt::Debug for Widget
 {
@@ -576,29 +1926,64 @@ pub struct Graphical
 
 impl Graphical
 {
+	/// connect to the display, panicking if that fails
 	pub fn new() -> Graphical
 	{
-		let conn = xcb::Connection::connect(None).unwrap();
-		let event_post = Arc::new(queue::EventPoster::new(&conn.0));
-		let pict_formats = pict_formats(&conn.0);
+		Graphical::try_new().unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// connect to the display named by `DISPLAY`
+	pub fn try_new() -> Result<Graphical>
+	{
+		let conn = xcb::Connection::connect(None)?;
+		let event_post = Arc::new(queue::EventPoster::new(&conn.0)?);
+		let executor = Executor::new(event_post.notify_fd());
+		let formats = window_formats(&conn.0, conn.1)?;
+		let keymap = Keymap::load(&conn.0)?;
 		let g = GraphicalDetails
 		{
 			connection : conn.0,
 			screen_num : conn.1,
-			top_level_widgets : vec!(),
-			repaint_everything : Cell::new(false),
+			top_level_widgets : RefCell::new(vec!()),
+			damage : RefCell::new(vec!()),
+			paint_origin : Cell::new(Point { x: 0, y: 0 }),
+			paint_region : RefCell::new(None),
+			back_buffers : RefCell::new(vec!()),
 			event_post,
-			pict_formats,
+			formats,
+			keymap: RefCell::new(keymap),
+			pending_event: RefCell::new(None),
+			last_key_release: Cell::new(None),
+			focus: RefCell::new(vec!()),
+			hovered: RefCell::new(vec!()),
+			mouse_grab: RefCell::new(None),
+			last_click: Cell::new(None),
+			double_click_interval: Cell::new(Duration::from_millis(400)),
+			drag_threshold: Cell::new(8),
+			timers: Timers::new(),
+			invoker: RefCell::new(None),
+			executor,
+			exit_code: Cell::new(None),
+			quit_on_last_window_closed: Cell::new(true),
+			about_to_quit_callbacks: RefCell::new(vec!()),
+			display_lost_callbacks: RefCell::new(vec!()),
+			deferred: RefCell::new(vec!()),
+			idle_callbacks: RefCell::new(vec!()),
+			colormaps: RefCell::new(vec!()),
+			wm_protocols: Cell::new(None),
+			animations: Animations::new(),
 		};
 		
-		Graphical
+		Ok(Graphical
 		{
 			det : Rc::new(RefCell::new(g)),
 			widget : WidgetBase::named("root"),
-		}
+		})
 	}
 
-	pub fn channel<T: 'static+Clone+Sized+Send, F: 'static+FnMut(T)>(
+	/// call `f` on the GUI thread with each value sent to the
+	/// returned `ChannelWrite`; `f` may return `false` to close it
+	pub fn channel<T: 'static+Sized+Send, F: 'static+FnMut(T) -> R, R: queue::HandlerResult>(
 		&self,
 		f: F,
 	) -> queue::ChannelWrite<T>
@@ -606,10 +1991,42 @@ impl Graphical
 		let a : &RefCell<GraphicalDetails> = self.det.borrow();
 		a.borrow().channel(f)
 	}
+
+	/// a handle that other threads can use to run
+	/// closures on the GUI thread
+	pub fn invoker(&self) -> queue::GuiInvoker
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().invoker()
+	}
+
+	/// like `channel`, but holds at most `capacity` values that
+	/// haven't been handled; `send` waits while it is full.
+	/// Panics if `capacity` is 0.
+	pub fn bounded_channel<T: 'static+Sized+Send, F: 'static+FnMut(T) -> R, R: queue::HandlerResult>(
+		&self,
+		capacity: usize,
+		f: F,
+	) -> queue::ChannelWrite<T>
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().bounded_channel(capacity, f)
+	}
 	
+	/// show `widget` in a new window, which is closed
+	/// when the returned widget is dropped
 	pub fn put<'a, W>(&'a self, widget: W)
 		-> Rc<W>
 	where W: Widget + 'static
+	{
+		self.try_put(widget).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// like `put`, but returns an error if the widget's window
+	/// can't be made
+	pub fn try_put<'a, W>(&'a self, widget: W)
+		-> Result<Rc<W>>
+	where W: Widget + 'static
 	{
 		let mut max_size = widget.maximum_size();
 		if max_size.width > (i32::max_value() as u32)
@@ -617,23 +2034,64 @@ impl Graphical
 		if max_size.height > (i32::max_value() as u32)
 			{ max_size.height = i32::max_value() as u32; }
 
-		widget.setup(self.det.clone());
+		widget.try_setup(self.det.clone())?;
 
 		let b = Rc::new(widget);
+		register_widget(&b);
 
-		self.det.borrow_mut().top_level_widgets.push( b.clone() );
-		b
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().add_top_level(b.clone());
+		Ok(b)
 	}
 
-	pub(crate) fn make_real_window(&self) -> u32
+	pub(crate) fn make_real_window(&self) -> Result<u32>
 	{
 		let a : &RefCell<GraphicalDetails> = self.det.borrow();
 		a.borrow().make_real_window()
 	}
-	pub fn exec(&self)
+	/// run the event loop until `quit` is called, and return the
+	/// code passed to it, or an error if the display is lost
+	/// or a window can't be painted
+	pub fn exec(&self) -> Result<i32>
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().exec()
+	}
+	/// make `exec` return `code`
+	pub fn quit(&self, code: i32)
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().quit(code);
+	}
+	/// whether to quit when the last window is closed, the default
+	pub fn set_quit_on_last_window_closed(&self, quit: bool)
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().set_quit_on_last_window_closed(quit);
+	}
+	/// call `cb` when `exec` is about to return
+	pub fn on_about_to_quit<F: 'static+FnMut()>(&self, cb: F)
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().on_about_to_quit(cb);
+	}
+	/// call `f` on the GUI thread once the current event is handled
+	pub fn invoke_later<F: 'static+FnOnce()>(&self, f: F)
 	{
 		let a : &RefCell<GraphicalDetails> = self.det.borrow();
-		a.borrow().exec();
+		a.borrow().invoke_later(f);
+	}
+	/// call `f` whenever the event loop is idle, until it returns false
+	pub fn on_idle<F: 'static+FnMut() -> bool>(&self, f: F)
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().on_idle(f);
+	}
+	/// call `cb` when the connection to the X server is lost
+	pub fn on_display_lost<F: 'static+FnMut(&Error)>(&self, cb: F)
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().on_display_lost(cb);
 	}
 	pub fn pixmap(&self, from: &image::DynamicImage)
 		-> cairo::surface::Surface
@@ -641,19 +2099,75 @@ impl Graphical
 		let a : &RefCell<GraphicalDetails> = self.det.borrow();
 		a.borrow().pixmap(from)
 	}
-}
+	/// copy `from` to a new pixmap on the X server
+	pub fn try_pixmap(&self, from: &image::DynamicImage)
+		-> Result<cairo::surface::Surface>
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().try_pixmap(from)
+	}
+
+	/// set the longest time between the clicks of a double click
+	pub fn set_double_click_interval(&self, interval: Duration)
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().set_double_click_interval(interval);
+	}
+	/// set how many pixels the pointer must move before a press becomes a drag
+	pub fn set_drag_threshold(&self, pixels: u32)
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().set_drag_threshold(pixels);
+	}
 
+	/// call `f` once on the GUI thread after `delay`
+	pub fn single_shot<F: 'static+FnOnce()>(&self, delay: Duration, f: F) -> Timer
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().single_shot(delay, f)
+	}
+	/// call `f` on the GUI thread every `interval` until the timer is cancelled
+	pub fn interval<F: 'static+FnMut()>(&self, interval: Duration, f: F) -> Timer
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().interval(interval, f)
+	}
 
-#[derive(PartialEq, Eq)]
-pub enum MouseEvent
-{
-	LeftPress,
-	LeftRelease,
-	RightPress,
-	RightRelease
+	/// call `f` on the GUI thread whenever `fd` is ready for `interest`
+	pub fn watch_fd<F: 'static+FnMut(queue::FdReady)>(
+		&self,
+		fd: std::os::unix::io::RawFd,
+		interest: queue::FdInterest,
+		f: F,
+	) -> queue::FdWatch
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().watch_fd(fd, interest, f)
+	}
+
+	/// run `future` on the GUI thread, where it can use widgets
+	pub fn spawn_local<F: 'static+std::future::Future<Output=()>>(&self, future: F)
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().spawn_local(future);
+	}
+	/// a future that completes after `delay`
+	pub fn sleep(&self, delay: Duration) -> Sleep
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().sleep(delay)
+	}
+
+	/// run `anim` from its start, stepping it every frame
+	pub fn animate(&self, anim: Rc<Animate>) -> AnimationHandle
+	{
+		let a : &RefCell<GraphicalDetails> = self.det.borrow();
+		a.borrow().animate(anim)
+	}
 }
 
 
+
 pub struct WidgetBase
 {
 	pub(crate) true_window_id: Cell<u32>,
@@ -661,6 +2175,12 @@ pub struct WidgetBase
 	pub(crate) rectangle : Cell<Rectangle>,
 	name : String,
 	maximum_size : Size,
+	this: RefCell<Option<Weak<Widget>>>,
+	focusable: Cell<bool>,
+	has_focus: Cell<bool>,
+	hovered: Cell<bool>,
+	size_changed: Signal<Size>,
+	parent: RefCell<Option<Weak<Widget>>>,
 }
 
 
@@ -676,6 +2196,12 @@ impl WidgetBase
 			rectangle: Cell::new(Rectangle::coords(0,0, 100, 100)),
 			name : name.to_string(),
 			maximum_size : Size{ width : u32::max_value(), height : u32::max_value() },
+			this: RefCell::new(None),
+			focusable: Cell::new(false),
+			has_focus: Cell::new(false),
+			hovered: Cell::new(false),
+			size_changed: Signal::new(),
+			parent: RefCell::new(None),
 		}
 	}
 	pub fn new() -> WidgetBase
@@ -687,6 +2213,37 @@ impl WidgetBase
 	{
 		self.true_window_id.get()
 	}
+
+	/// the widget that contains this one, if any
+	pub fn parent(&self) -> Option<Rc<Widget>>
+	{
+		self.parent.borrow().as_ref().and_then(|w| w.upgrade())
+	}
+	pub(crate) fn clear_parent(&self)
+	{
+		*self.parent.borrow_mut() = None;
+	}
+
+	/// emitted with the new size when the widget's size changes
+	pub fn size_changed(&self) -> &Signal<Size>
+	{
+		&self.size_changed
+	}
+	/// emit `size_changed` if the size is no longer `old`
+	pub(crate) fn notify_resized(&self, old: Size)
+	{
+		let sz = self.rectangle.get().size;
+		if sz != old
+		{
+			self.size_changed.emit(&sz);
+		}
+	}
+
+	/// the `Rc` this widget lives in, once it has been put somewhere
+	pub fn this(&self) -> Option<Rc<Widget>>
+	{
+		self.this.borrow().as_ref().and_then(|w| w.upgrade())
+	}
 	
 	fn rectangle(&self) -> Rectangle
 	{
@@ -709,31 +2266,211 @@ impl WidgetBase
 		self.maximum_size = size;
 	}
 	
-	fn repaint(&self)
+	fn repaint(&self, rect: Rectangle)
 	{
 		let d = self.det.borrow();
 		if let Some(ref a) = *d
 		{
 			let a : &RefCell<GraphicalDetails> = a.borrow();
-			a.borrow().repaint_everything();
+			a.borrow().damage_widget(self, rect);
+		}
+	}
+}
+
+/// Containers call this on each widget they wrap in an `Rc`,
+/// so that the widget can refer to itself later
+pub fn register_widget<W: Widget + 'static>(widget: &Rc<W>)
+{
+	let w : Rc<Widget> = widget.clone();
+	*widget.as_widget().this.borrow_mut() = Some(Rc::downgrade(&w));
+
+	// children added before now couldn't know their parent
+	for c in widget.children()
+	{
+		set_parent(c.as_widget(), widget.as_widget());
+	}
+}
+
+/// let `w` know its own `Rc`, if it doesn't yet
+fn register_child(w: &Rc<Widget>)
+{
+	let mut this = w.as_widget().this.borrow_mut();
+	if this.as_ref().and_then(|t| t.upgrade()).is_none()
+	{
+		*this = Some(Rc::downgrade(w));
+	}
+}
+
+/// make `parent` the parent of `child`; if `parent` isn't registered
+/// yet, `register_widget` sets it later
+pub fn set_parent(child: &WidgetBase, parent: &WidgetBase)
+{
+	*child.parent.borrow_mut() = parent.this.borrow().clone();
+}
+
+/// Draw each visible child of `w` with `draw_child`
+pub fn draw_children<W: Widget + ?Sized>(c: &mut cairo::Cairo, w: &W)
+{
+	for child in w.visible_children()
+	{
+		draw_child(c, &*child);
+	}
+}
+
+/// Draw `child` of the widget being drawn on `c` at its position
+/// and clipped to its size; containers call this from `draw`
+pub fn draw_child(c: &mut cairo::Cairo, child: &Widget)
+{
+	if let Some(det) = child.det()
+	{
+		let det : &RefCell<GraphicalDetails> = det.borrow();
+		det.borrow().draw_child(c, child);
+	}
+}
+
+pub(crate) fn intern_atom(conn: &xcb::Connection, atom_name: &str)
+	-> Result<xcb::Atom>
+{
+	Ok(xcb::intern_atom(conn, false, atom_name).get_reply()?.atom())
+}
+
+pub(crate) fn same_widget(a: &Widget, b: &Widget) -> bool
+{
+	std::ptr::eq(a.as_widget(), b.as_widget())
+}
+
+/// add `rect` to `region`, merging overlapping areas
+/// to keep the region small
+fn add_damage(region: &mut Vec<Rectangle>, mut rect: Rectangle)
+{
+	while let Some(i) = region.iter().position(|r| r.intersects(&rect))
+	{
+		rect = rect.united(&region.remove(i));
+	}
+	region.push(rect);
+
+	if region.len() > 16
+	{
+		let all = region.iter().fold(rect, |a, r| a.united(r));
+		*region = vec!(all);
+	}
+}
+
+/// the position of `w` relative to `ancestor`,
+/// if `ancestor` contains it
+fn origin_in(ancestor: &Rc<Widget>, w: &WidgetBase, origin: Point) -> Option<Point>
+{
+	if std::ptr::eq(ancestor.as_widget(), w)
+	{
+		return Some(origin);
+	}
+	for c in ancestor.children()
+	{
+		if let Some(o) = origin_in(&c, w, c.map_to_parent(origin))
+		{
+			return Some(o);
 		}
 	}
+	None
+}
+
+/// the widget of the window that `w` is in, and `pt`
+/// in `w`'s coordinates mapped to that window's
+fn to_window(w: &WidgetBase, mut pt: Point) -> Option<(Rc<Widget>, Point)>
+{
+	let mut w = w.this()?;
+	loop
+	{
+		if w.as_widget().true_window_id() != 0
+			{ return Some((w, pt)); }
+		pt = w.map_to_parent(pt);
+		w = w.parent()?;
+	}
+}
+
+fn focus_chain(w: &Rc<Widget>, chain: &mut Vec<Rc<Widget>>)
+{
+	if w.accepts_focus()
+	{
+		chain.push(w.clone());
+	}
+	for c in w.visible_children()
+	{
+		focus_chain(&c, chain);
+	}
 }
 
 trait Descending
 {
-	fn descendant_at(&self, pt: &Point) -> Rc<Widget>;
+	fn path_at(&self, pt: &Point) -> Vec<(Rc<Widget>, Point)>;
 }
 
 impl Descending for Rc<Widget>
 {
-	fn descendant_at(&self, pt: &Point) -> Rc<Widget>
+	/// the widgets from `self` down to the innermost one
+	/// under `pt`, each with `pt` in its own coordinates
+	fn path_at(&self, pt: &Point) -> Vec<(Rc<Widget>, Point)>
+	{
+		let mut path = vec!((self.clone(), *pt));
+		loop
+		{
+			let (child, pt) =
+			{
+				let &(ref w, pt) = path.last().unwrap();
+				match w.child_at(&pt)
+				{
+					Some(c) =>
+					{
+						let pt = c.map_from_parent(pt);
+						(c, pt)
+					},
+					None => break,
+				}
+			};
+			path.push((child, pt));
+		}
+		path
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn damage_merges_overlapping()
+	{
+		let mut region = vec!();
+		add_damage(&mut region, Rectangle::coords(0, 0, 10, 10));
+		add_damage(&mut region, Rectangle::coords(50, 50, 10, 10));
+		assert_eq!(region.len(), 2);
+
+		// overlaps both, so all three become one
+		add_damage(&mut region, Rectangle::coords(5, 5, 50, 50));
+		assert_eq!(region, vec!(Rectangle::coords(0, 0, 60, 60)));
+	}
+
+	#[test]
+	fn damage_merges_chains()
+	{
+		let mut region = vec!();
+		add_damage(&mut region, Rectangle::coords(0, 0, 10, 10));
+		add_damage(&mut region, Rectangle::coords(8, 12, 10, 10));
+		// only overlaps the first, but the union of
+		// them overlaps the second
+		add_damage(&mut region, Rectangle::coords(2, 2, 3, 11));
+		assert_eq!(region, vec!(Rectangle::coords(0, 0, 18, 22)));
+	}
+
+	#[test]
+	fn damage_is_bounded()
 	{
-		eprintln!("getting children on {}", self.name());
-		match (*self).child_at(pt)
+		let mut region = vec!();
+		for i in 0..17
 		{
-			Some(a) => a.descendant_at(pt),
-			None => self.clone(),
+			add_damage(&mut region, Rectangle::coords(i*20, 0, 10, 10));
 		}
+		assert_eq!(region, vec!(Rectangle::coords(0, 0, 16*20+10, 10)));
 	}
 }
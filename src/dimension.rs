@@ -1,11 +1,11 @@
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Point
 {
 	pub x : i32,
 	pub y : i32,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Size
 {
 	pub width : u32,
@@ -32,7 +32,7 @@ impl Size
 	}
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Rectangle
 {
 	pub pos : Point,
@@ -64,7 +64,7 @@ impl Rectangle
 	{
 		let mut point = *point;
 		point.x -= self.pos.x;
-		point.x -= self.pos.y;
+		point.y -= self.pos.y;
 		point
 	}
 
@@ -84,6 +84,50 @@ impl Rectangle
 	{
 		self.size.height
 	}
+	pub fn is_empty(&self) -> bool
+	{
+		self.width() == 0 || self.height() == 0
+	}
+
+	pub fn intersects(&self, other: &Rectangle) -> bool
+	{
+		self.intersection(other).is_some()
+	}
+
+	/// the area covered by both `self` and `other`
+	pub fn intersection(&self, other: &Rectangle) -> Option<Rectangle>
+	{
+		let x1 = self.x().max(other.x());
+		let y1 = self.y().max(other.y());
+		let x2 = (self.x() + self.width() as i32).min(other.x() + other.width() as i32);
+		let y2 = (self.y() + self.height() as i32).min(other.y() + other.height() as i32);
+		if x2 <= x1 || y2 <= y1
+		{
+			return None;
+		}
+		Some(Rectangle::coords(x1, y1, (x2-x1) as u32, (y2-y1) as u32))
+	}
+
+	/// the smallest rectangle that covers both `self` and `other`
+	pub fn united(&self, other: &Rectangle) -> Rectangle
+	{
+		if self.is_empty() { return *other; }
+		if other.is_empty() { return *self; }
+		let x1 = self.x().min(other.x());
+		let y1 = self.y().min(other.y());
+		let x2 = (self.x() + self.width() as i32).max(other.x() + other.width() as i32);
+		let y2 = (self.y() + self.height() as i32).max(other.y() + other.height() as i32);
+		Rectangle::coords(x1, y1, (x2-x1) as u32, (y2-y1) as u32)
+	}
+
+	pub fn translated(&self, by: &Point) -> Rectangle
+	{
+		let mut r = *self;
+		r.pos.x += by.x;
+		r.pos.y += by.y;
+		r
+	}
+
 	pub fn set_position(&mut self, pos: &Point)
 	{
 		self.pos = *pos;
@@ -93,3 +137,61 @@ impl Rectangle
 		self.size = *sz;
 	}
 }
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn map_to()
+	{
+		let r = Rectangle::coords(10, 20, 100, 100);
+		assert_eq!(r.map_to(&Point { x: 15, y: 40 }), Point { x: 5, y: 20 });
+		assert_eq!(r.map_to(&Point { x: 0, y: 0 }), Point { x: -10, y: -20 });
+	}
+
+	#[test]
+	fn contains()
+	{
+		let r = Rectangle::coords(10, 20, 5, 5);
+		assert!(r.contains(&Point { x: 10, y: 20 }));
+		assert!(r.contains(&Point { x: 14, y: 24 }));
+		assert!(!r.contains(&Point { x: 15, y: 24 }));
+		assert!(!r.contains(&Point { x: 14, y: 25 }));
+		assert!(!r.contains(&Point { x: 9, y: 20 }));
+	}
+
+	#[test]
+	fn intersection()
+	{
+		let a = Rectangle::coords(0, 0, 10, 10);
+		let b = Rectangle::coords(5, 8, 10, 10);
+		assert_eq!(a.intersection(&b), Some(Rectangle::coords(5, 8, 5, 2)));
+		assert_eq!(b.intersection(&a), a.intersection(&b));
+
+		// touching edges don't overlap
+		let c = Rectangle::coords(10, 0, 10, 10);
+		assert_eq!(a.intersection(&c), None);
+		assert!(!a.intersects(&c));
+	}
+
+	#[test]
+	fn united()
+	{
+		let a = Rectangle::coords(0, 0, 10, 10);
+		let b = Rectangle::coords(20, 5, 5, 20);
+		assert_eq!(a.united(&b), Rectangle::coords(0, 0, 25, 25));
+
+		let empty = Rectangle::coords(100, 100, 0, 0);
+		assert_eq!(a.united(&empty), a);
+		assert_eq!(empty.united(&a), a);
+	}
+
+	#[test]
+	fn translated()
+	{
+		let r = Rectangle::coords(1, 2, 3, 4);
+		assert_eq!(r.translated(&Point { x: 10, y: -20 }), Rectangle::coords(11, -18, 3, 4));
+	}
+}
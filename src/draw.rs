@@ -32,6 +32,13 @@ impl Color
 		Color { r:r as f64/255.0, g:g as f64/255.0, b:b as f64/255.0, a:a as f64/255.0}
 	}
 
+	/// the color `t` of the way from this color to `other`
+	pub fn mix(&self, other: &Color, t: f64) -> Color
+	{
+		let m = |a: f64, b: f64| a + (b-a)*t;
+		Color { r:m(self.r, other.r), g:m(self.g, other.g), b:m(self.b, other.b), a:m(self.a, other.a) }
+	}
+
 	pub fn xcb_color(&self) -> u32
 	{
 		let mut v = 0u32;
@@ -63,6 +70,7 @@ pub trait DrawPixel
 	fn vgradient(&mut self, y1 : i32, y2 : i32, c1 : Color, c2 : Color);
 	fn fillcolor(&mut self, c : Color);
 	fn paste(&mut self, src: &cairo::surface::Surface);
+	fn focus_rect(&mut self, x : i32, y : i32, width : i32, height : i32);
 }
 
 impl DrawPixel for cairo::Cairo
@@ -119,4 +127,22 @@ impl DrawPixel for cairo::Cairo
 		self.restore();
 
 	}
+
+	fn focus_rect(&mut self, x : i32, y : i32, width : i32, height : i32)
+	{
+		self.save();
+		self.set_antialias(cairo::antialias::Antialias::None);
+		self.set_color(Color::rgba(0x33, 0x30, 0x2e, 0xc0));
+		for i in (x..x+width).step_by(2)
+		{
+			self.pixel(i, y);
+			self.pixel(i, y+height-1);
+		}
+		for i in (y..y+height).step_by(2)
+		{
+			self.pixel(x, i);
+			self.pixel(x+width-1, i);
+		}
+		self.restore();
+	}
 }
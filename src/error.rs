@@ -0,0 +1,74 @@
+use std::fmt;
+
+/// The ways talking to the display can fail
+#[derive(Debug)]
+pub enum Error
+{
+	/// connecting to the X server failed, usually because there
+	/// is no display or `DISPLAY` is not set
+	Connection(xcb::ConnError),
+	/// the connection to the X server broke, usually because
+	/// the server exited
+	ConnectionLost(xcb::ConnError),
+	/// the X server has no render formats for the screen
+	NoPictFormat,
+	/// the X server has no visual of a supported depth
+	NoVisual,
+	/// an X request failed with this error code
+	Request(u8),
+	/// cairo failed to create a surface
+	Cairo(cairo::Status),
+	/// a system call failed
+	Sys(nix::Error),
+	/// the event loop that should have received a
+	/// closure or value is gone
+	Disconnected,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			Error::Connection(ref e) => write!(f, "cannot connect to the display: {}", e),
+			Error::ConnectionLost(ref e) => write!(f, "lost the connection to the display: {}", e),
+			Error::NoPictFormat => write!(f, "no usable render picture format"),
+			Error::NoVisual => write!(f, "no usable visual"),
+			Error::Request(code) => write!(f, "X request failed with error code {}", code),
+			Error::Cairo(ref s) => write!(f, "error creating cairo surface {:?}", s),
+			Error::Sys(ref e) => write!(f, "{}", e),
+			Error::Disconnected => write!(f, "the event loop is gone"),
+		}
+	}
+}
+
+impl std::error::Error for Error
+{
+}
+
+impl From<xcb::ConnError> for Error
+{
+	fn from(e: xcb::ConnError) -> Error
+	{
+		Error::Connection(e)
+	}
+}
+
+impl From<xcb::GenericError> for Error
+{
+	fn from(e: xcb::GenericError) -> Error
+	{
+		Error::Request(e.error_code())
+	}
+}
+
+impl From<nix::Error> for Error
+{
+	fn from(e: nix::Error) -> Error
+	{
+		Error::Sys(e)
+	}
+}
@@ -0,0 +1,312 @@
+use antidote::Mutex;
+
+use std::rc::Rc;
+use std::cell::{RefCell, Cell};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::time::Duration;
+use std::os::unix::io::RawFd;
+
+use crate::timer::*;
+
+type LocalFuture = Pin<Box<Future<Output=()>>>;
+
+enum Task
+{
+	Free,
+	/// being polled by `run_ready`; the slot stays
+	/// reserved so that `spawn` can't reuse it
+	Running,
+	Waiting(LocalFuture),
+}
+
+/// Runs futures on the GUI thread; woken tasks are
+/// polled by the event loop
+pub(crate) struct Executor
+{
+	tasks: RefCell<Vec<Task>>,
+	ready: Arc<Mutex<Vec<usize>>>,
+	event_w_fd: RawFd,
+}
+
+impl Executor
+{
+	/// `event_w_fd` is written to on every wake, so
+	/// that the event loop stops waiting
+	pub(crate) fn new(event_w_fd: RawFd) -> Executor
+	{
+		Executor
+		{
+			tasks: RefCell::new(vec!()),
+			ready: Arc::new(Mutex::new(vec!())),
+			event_w_fd,
+		}
+	}
+
+	pub(crate) fn spawn(&self, future: LocalFuture)
+	{
+		let id;
+		{
+			let mut tasks = self.tasks.borrow_mut();
+			match tasks.iter().position(|t| if let Task::Free = t { true } else { false })
+			{
+				Some(free) =>
+				{
+					tasks[free] = Task::Waiting(future);
+					id = free;
+				},
+				None =>
+				{
+					tasks.push(Task::Waiting(future));
+					id = tasks.len()-1;
+				},
+			}
+		}
+		self.wake_target(id).wake();
+	}
+
+	fn wake_target(&self, id: usize) -> WakeTarget
+	{
+		WakeTarget
+		{
+			id,
+			ready: self.ready.clone(),
+			event_w_fd: self.event_w_fd,
+		}
+	}
+
+	/// poll the tasks that were woken
+	pub(crate) fn run_ready(&self)
+	{
+		// tasks woken while these run are polled on the next
+		// iteration of the event loop, so a task that wakes
+		// itself can't starve it
+		let ready = std::mem::replace(&mut *self.ready.lock(), vec!());
+
+		for id in ready
+		{
+			// take the task out while polling it, so that it
+			// can spawn other tasks; its slot is marked as
+			// running so they don't get put in it
+			let mut task =
+			{
+				let mut tasks = self.tasks.borrow_mut();
+				match tasks.get_mut(id)
+				{
+					Some(t @ Task::Waiting(_)) =>
+						match std::mem::replace(t, Task::Running)
+						{
+							Task::Waiting(f) => f,
+							_ => unreachable!(),
+						},
+					_ => continue,
+				}
+			};
+
+			let waker = waker(Arc::new(self.wake_target(id)));
+			let mut cx = Context::from_waker(&waker);
+			let pending = task.as_mut().poll(&mut cx).is_pending();
+			self.tasks.borrow_mut()[id] =
+				if pending { Task::Waiting(task) } else { Task::Free };
+		}
+	}
+}
+
+struct WakeTarget
+{
+	id: usize,
+	ready: Arc<Mutex<Vec<usize>>>,
+	event_w_fd: RawFd,
+}
+
+impl WakeTarget
+{
+	fn wake(&self)
+	{
+		{
+			let mut ready = self.ready.lock();
+			if ready.contains(&self.id) { return; }
+			ready.push(self.id);
+		}
+		let _ = nix::unistd::write(self.event_w_fd, b"1");
+	}
+}
+
+static WAKER_VTABLE : RawWakerVTable = RawWakerVTable::new(
+	clone_waker,
+	wake_waker,
+	wake_waker_by_ref,
+	drop_waker,
+);
+
+fn waker(target: Arc<WakeTarget>) -> Waker
+{
+	let raw = RawWaker::new(Arc::into_raw(target) as *const (), &WAKER_VTABLE);
+	unsafe { Waker::from_raw(raw) }
+}
+
+unsafe fn clone_waker(p: *const ()) -> RawWaker
+{
+	let target = Arc::from_raw(p as *const WakeTarget);
+	let clone = target.clone();
+	std::mem::forget(target);
+	RawWaker::new(Arc::into_raw(clone) as *const (), &WAKER_VTABLE)
+}
+
+unsafe fn wake_waker(p: *const ())
+{
+	let target = Arc::from_raw(p as *const WakeTarget);
+	target.wake();
+}
+
+unsafe fn wake_waker_by_ref(p: *const ())
+{
+	(*(p as *const WakeTarget)).wake();
+}
+
+unsafe fn drop_waker(p: *const ())
+{
+	drop(Arc::from_raw(p as *const WakeTarget));
+}
+
+struct SleepState
+{
+	done: Cell<bool>,
+	waker: RefCell<Option<Waker>>,
+}
+
+/// A future that completes after a delay, made by `sleep`
+pub struct Sleep
+{
+	state: Rc<SleepState>,
+	timer: Timer,
+}
+
+impl Sleep
+{
+	pub(crate) fn new(timers: &Timers, delay: Duration) -> Sleep
+	{
+		let state = Rc::new(
+			SleepState
+			{
+				done: Cell::new(false),
+				waker: RefCell::new(None),
+			}
+		);
+		let s = state.clone();
+		let timer = timers.add(
+			delay,
+			None,
+			Box::new(
+				move ||
+				{
+					s.done.set(true);
+					if let Some(w) = s.waker.borrow_mut().take()
+						{ w.wake(); }
+				}
+			),
+		);
+		Sleep { state, timer }
+	}
+}
+
+impl Future for Sleep
+{
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()>
+	{
+		if self.state.done.get()
+		{
+			return Poll::Ready(());
+		}
+		*self.state.waker.borrow_mut() = Some(cx.waker().clone());
+		Poll::Pending
+	}
+}
+
+impl Drop for Sleep
+{
+	fn drop(&mut self)
+	{
+		self.timer.cancel();
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	/// a value set by one task and awaited by another
+	struct Slot
+	{
+		value: Cell<Option<i32>>,
+		waker: RefCell<Option<Waker>>,
+	}
+
+	struct Get(Rc<Slot>);
+
+	impl Future for Get
+	{
+		type Output = i32;
+
+		fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<i32>
+		{
+			match self.0.value.get()
+			{
+				Some(v) => Poll::Ready(v),
+				None =>
+				{
+					*self.0.waker.borrow_mut() = Some(cx.waker().clone());
+					Poll::Pending
+				},
+			}
+		}
+	}
+
+	#[test]
+	fn task_spawns_and_awaits_task()
+	{
+		let (r, w) = nix::unistd::pipe2(nix::fcntl::OFlag::O_NONBLOCK).unwrap();
+		let executor = Rc::new(Executor::new(w));
+		let result = Rc::new(Cell::new(None));
+
+		{
+			let executor2 = executor.clone();
+			let result = result.clone();
+			executor.spawn(
+				Box::pin(
+					async move
+					{
+						let slot = Rc::new(Slot { value: Cell::new(None), waker: RefCell::new(None) });
+						let s = slot.clone();
+						executor2.spawn(
+							Box::pin(
+								async move
+								{
+									s.value.set(Some(42));
+									if let Some(w) = s.waker.borrow_mut().take()
+										{ w.wake(); }
+								}
+							)
+						);
+						result.set(Some(Get(slot).await));
+					}
+				)
+			);
+		}
+
+		for _ in 0..10
+		{
+			executor.run_ready();
+		}
+		assert_eq!(result.get(), Some(42));
+		assert!(executor.tasks.borrow().iter().all(|t| if let Task::Free = t { true } else { false }));
+
+		let _ = nix::unistd::close(r);
+		let _ = nix::unistd::close(w);
+	}
+}
@@ -0,0 +1,273 @@
+/// Keysym values for keys that don't produce text,
+/// as defined in X11's `keysymdef.h`
+pub mod keysym
+{
+	pub const BACKSPACE : u32 = 0xff08;
+	pub const TAB : u32 = 0xff09;
+	pub const ISO_LEFT_TAB : u32 = 0xfe20;
+	pub const RETURN : u32 = 0xff0d;
+	pub const ESCAPE : u32 = 0xff1b;
+	pub const DELETE : u32 = 0xffff;
+	pub const HOME : u32 = 0xff50;
+	pub const LEFT : u32 = 0xff51;
+	pub const UP : u32 = 0xff52;
+	pub const RIGHT : u32 = 0xff53;
+	pub const DOWN : u32 = 0xff54;
+	pub const PAGE_UP : u32 = 0xff55;
+	pub const PAGE_DOWN : u32 = 0xff56;
+	pub const END : u32 = 0xff57;
+	pub const KP_ENTER : u32 = 0xff8d;
+	pub const SPACE : u32 = 0x0020;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyAction
+{
+	Press,
+	Release,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Modifiers
+{
+	pub shift : bool,
+	pub control : bool,
+	pub alt : bool,
+	pub logo : bool,
+	pub caps_lock : bool,
+	pub num_lock : bool,
+}
+
+impl Modifiers
+{
+	/// decode the `state` field of an X input event
+	pub fn from_x_state(state: u16) -> Modifiers
+	{
+		let state = state as u32;
+		Modifiers
+		{
+			shift : state & xcb::MOD_MASK_SHIFT != 0,
+			control : state & xcb::MOD_MASK_CONTROL != 0,
+			alt : state & xcb::MOD_MASK_1 != 0,
+			logo : state & xcb::MOD_MASK_4 != 0,
+			caps_lock : state & xcb::MOD_MASK_LOCK != 0,
+			num_lock : state & xcb::MOD_MASK_2 != 0,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyEvent
+{
+	pub action : KeyAction,
+	pub keysym : u32,
+	pub keycode : u8,
+	/// the text this key types, empty for keys like arrows
+	pub text : String,
+	pub modifiers : Modifiers,
+	/// set if this event was generated by holding the key down
+	pub autorepeat : bool,
+	pub time : u32,
+}
+
+impl KeyEvent
+{
+	pub fn is_press(&self) -> bool
+	{
+		self.action == KeyAction::Press
+	}
+	pub fn is_release(&self) -> bool
+	{
+		self.action == KeyAction::Release
+	}
+}
+
+/// The keycode to keysym table of the X server
+pub(crate) struct Keymap
+{
+	min_keycode : u8,
+	keysyms_per_keycode : usize,
+	keysyms : Vec<u32>,
+}
+
+impl Keymap
+{
+	pub(crate) fn load(conn: &xcb::base::Connection)
+		-> crate::error::Result<Keymap>
+	{
+		let setup = conn.get_setup();
+		let min_keycode = setup.min_keycode();
+		let count = setup.max_keycode() - min_keycode + 1;
+
+		let r = xcb::get_keyboard_mapping(conn, min_keycode, count).get_reply()?;
+		Ok(Keymap
+		{
+			min_keycode,
+			keysyms_per_keycode : r.keysyms_per_keycode() as usize,
+			keysyms : r.keysyms().to_vec(),
+		})
+	}
+
+	fn column(&self, keycode: u8, col: usize) -> u32
+	{
+		if keycode < self.min_keycode || col >= self.keysyms_per_keycode
+			{ return 0; }
+		let idx = (keycode - self.min_keycode) as usize * self.keysyms_per_keycode + col;
+		self.keysyms.get(idx).cloned().unwrap_or(0)
+	}
+
+	/// select the keysym from the first group for a keycode,
+	/// following the rules of the core protocol
+	pub(crate) fn keysym(&self, keycode: u8, modifiers: &Modifiers) -> u32
+	{
+		let lower = self.column(keycode, 0);
+		let mut upper = self.column(keycode, 1);
+		if upper == 0
+		{
+			upper = upper_case(lower);
+		}
+
+		let is_keypad = upper >= 0xff80 && upper <= 0xffbd;
+		if is_keypad && modifiers.num_lock
+		{
+			return if modifiers.shift { lower } else { upper };
+		}
+
+		// caps lock only changes the case of letters
+		let is_letter = upper != lower && upper == upper_case(lower);
+		let shifted = modifiers.shift
+			^ (modifiers.caps_lock && is_letter);
+		if shifted { upper } else { lower }
+	}
+}
+
+fn upper_case(keysym: u32) -> u32
+{
+	match keysym
+	{
+		0x61 ..= 0x7a => keysym - 0x20,
+		0xe0 ..= 0xfe if keysym != 0xf7 => keysym - 0x20,
+		0x01000000 ..= 0x0110ffff =>
+		{
+			std::char::from_u32(keysym - 0x01000000)
+				.and_then(|c| c.to_uppercase().next())
+				.map(|c| c as u32 + 0x01000000)
+				.unwrap_or(keysym)
+		},
+		_ => keysym,
+	}
+}
+
+/// the text typed by `keysym`, for the keysyms that have one
+pub(crate) fn keysym_to_text(keysym: u32) -> String
+{
+	let c = match keysym
+	{
+		0x20 ..= 0x7e | 0xa0 ..= 0xff => std::char::from_u32(keysym),
+		0x01000000 ..= 0x0110ffff => std::char::from_u32(keysym - 0x01000000),
+		0xffaa ..= 0xffb9 => std::char::from_u32(keysym - 0xff80),
+		_ => None,
+	};
+	c.map(|c| c.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	const KEY_A : u8 = 10;
+	const KEY_1 : u8 = 11;
+	const KEY_KP_1 : u8 = 12;
+	const KEY_E_ACUTE : u8 = 13;
+
+	fn keymap() -> Keymap
+	{
+		Keymap
+		{
+			min_keycode : KEY_A,
+			keysyms_per_keycode : 2,
+			keysyms : vec!(
+				// only the lower case is listed
+				0x61, 0,
+				0x31, 0x21,
+				// KP_End and KP_1
+				0xff9c, 0xffb1,
+				0xe9, 0,
+			),
+		}
+	}
+
+	fn mods(shift: bool, caps_lock: bool, num_lock: bool) -> Modifiers
+	{
+		Modifiers { shift, caps_lock, num_lock, ..Modifiers::default() }
+	}
+
+	#[test]
+	fn shift()
+	{
+		let k = keymap();
+		assert_eq!(k.keysym(KEY_A, &mods(false, false, false)), 0x61);
+		assert_eq!(k.keysym(KEY_A, &mods(true, false, false)), 0x41);
+		assert_eq!(k.keysym(KEY_1, &mods(false, false, false)), 0x31);
+		assert_eq!(k.keysym(KEY_1, &mods(true, false, false)), 0x21);
+		assert_eq!(k.keysym(KEY_E_ACUTE, &mods(true, false, false)), 0xc9);
+	}
+
+	#[test]
+	fn caps_lock()
+	{
+		let k = keymap();
+		assert_eq!(k.keysym(KEY_A, &mods(false, true, false)), 0x41);
+		assert_eq!(k.keysym(KEY_A, &mods(true, true, false)), 0x61);
+		// only letters are affected
+		assert_eq!(k.keysym(KEY_1, &mods(false, true, false)), 0x31);
+		assert_eq!(k.keysym(KEY_1, &mods(true, true, false)), 0x21);
+	}
+
+	#[test]
+	fn keypad()
+	{
+		let k = keymap();
+		assert_eq!(k.keysym(KEY_KP_1, &mods(false, false, false)), 0xff9c);
+		assert_eq!(k.keysym(KEY_KP_1, &mods(false, false, true)), 0xffb1);
+		// shift undoes num lock
+		assert_eq!(k.keysym(KEY_KP_1, &mods(true, false, true)), 0xff9c);
+		assert_eq!(k.keysym(KEY_KP_1, &mods(false, true, true)), 0xffb1);
+	}
+
+	#[test]
+	fn unknown_keycode()
+	{
+		let k = keymap();
+		assert_eq!(k.keysym(KEY_A-1, &mods(false, false, false)), 0);
+		assert_eq!(k.keysym(KEY_E_ACUTE+1, &mods(true, false, false)), 0);
+	}
+
+	#[test]
+	fn upper_case_of_keysyms()
+	{
+		assert_eq!(upper_case(0x7a), 0x5a);
+		assert_eq!(upper_case(0x5a), 0x5a);
+		assert_eq!(upper_case(0xe9), 0xc9);
+		// division sign
+		assert_eq!(upper_case(0xf7), 0xf7);
+		// greek alpha, as a unicode keysym
+		assert_eq!(upper_case(0x010003b1), 0x01000391);
+		assert_eq!(upper_case(keysym::LEFT), keysym::LEFT);
+	}
+
+	#[test]
+	fn text_of_keysyms()
+	{
+		assert_eq!(keysym_to_text(0x61), "a");
+		assert_eq!(keysym_to_text(keysym::SPACE), " ");
+		assert_eq!(keysym_to_text(0xe9), "\u{e9}");
+		assert_eq!(keysym_to_text(0x010020ac), "\u{20ac}");
+		// KP_1 and KP_Add
+		assert_eq!(keysym_to_text(0xffb1), "1");
+		assert_eq!(keysym_to_text(0xffab), "+");
+		assert_eq!(keysym_to_text(keysym::LEFT), "");
+		assert_eq!(keysym_to_text(keysym::RETURN), "");
+	}
+}
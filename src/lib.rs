@@ -9,6 +9,13 @@ mod queue;
 mod tabbar;
 mod tabwidget;
 mod container;
+mod keyboard;
+mod mouse;
+mod timer;
+mod animation;
+mod error;
+mod executor;
+mod signal;
 
 pub use crate::widgets::LineEdit;
 pub use crate::core::*;
@@ -20,5 +27,12 @@ pub use crate::pushbutton::*;
 pub use crate::tabbar::*;
 pub use crate::tabwidget::*;
 pub use crate::container::*;
+pub use crate::keyboard::*;
+pub use crate::mouse::*;
+pub use crate::animation::*;
 
-pub use crate::queue::ChannelWrite;
+pub use crate::error::Error;
+pub use crate::executor::Sleep;
+pub use crate::signal::*;
+pub use crate::queue::{ChannelWrite, HandlerResult, GuiInvoker, FdInterest, FdReady, FdWatch};
+pub use crate::timer::Timer;
@@ -7,14 +7,13 @@ pub struct MainWindow
 	widget: WidgetBase,
 	title: RefCell<String>,
 	child_widgets: RefCell<std::vec::Vec<Rc<Widget>>>,
+	close_requested_callbacks: RefCell<Vec<Box<FnMut() -> bool>>>,
 }
 
 fn intern_atom(conn: &xcb::Connection, atom_name: &str)
 	-> xcb::Atom
 {
-	xcb::intern_atom(conn, false, atom_name).get_reply()
-		.unwrap()
-		.atom()
+	crate::core::intern_atom(conn, atom_name).unwrap()
 }
 
 
@@ -29,68 +28,49 @@ impl Widget for MainWindow
 		&mut self.widget
 	}
 
+	/// panics if the window can't be made, use `try_setup`
+	/// to handle that
 	fn setup(&self, det: Rc<RefCell<GraphicalDetails>>)
 	{
+		self.try_setup(det).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	fn try_setup(&self, det: Rc<RefCell<GraphicalDetails>>) -> Result<(), Error>
+	{
+		// on failure, this is left as it was, without a window
+		let window = det.borrow().make_real_window()?;
 		*self.as_widget().det.borrow_mut() = Some(det.clone());
-		self.widget.true_window_id.set(det.borrow_mut().make_real_window());
+		self.widget.true_window_id.set(window);
 		self.setup_children(det);
 		self.setup_title();
+		Ok(())
 	}
 
-	fn setup_children(&self, det: Rc<RefCell<GraphicalDetails>>)
+	fn children(&self) -> Vec<Rc<Widget>>
 	{
-		for c in self.child_widgets.borrow_mut().iter_mut()
-		{
-			c.setup(det.clone());
-		}
-		/*
-		let hints = xcb_util::icccm::SizeHints::empty()
-			.max_size(max_size.width as i32, max_size.height as i32)
-			.build();
-
-		xcb_util::icccm::set_wm_normal_hints(
-			&mut gd.borrow_mut().connection,
-			new_real_window,
-			&hints
-		); */
+		self.child_widgets.borrow().clone()
 	}
-	fn draw(&self, c: &mut cairo::Cairo)
+
+	fn resized(&self, _sz: Size)
 	{
-		use std::borrow::Borrow;
-		for w in self.child_widgets.borrow().iter()
-		{
-			let wrect = w.rectangle();
-			c.save();
-			c.translate(wrect.x() as f64, wrect.y() as f64);
-			c.rectangle(0.0, 0.0, wrect.width() as f64, wrect.height() as f64);
-			c.clip();
-
-			let w = w.as_ref().borrow();
-			w.draw(c);
-			c.restore();
-		}
 	}
-
-	fn child_at(&self, pt: &Point) -> Option<Rc<Widget>>
+	fn close_event(&self) -> bool
 	{
-		for w in self.child_widgets.borrow().iter()
+		let mut accept = true;
+		for c in self.close_requested_callbacks.borrow_mut().iter_mut()
 		{
-			if w.rectangle().contains(pt)
-			{
-				return Some(w.clone());
-			}
+			accept = c() && accept;
 		}
-		None
-	}
-	fn resized(&self, _sz: Size)
-	{
+		accept
 	}
 	fn resize(&self, sz: &Size)
 	{
 		let mut rect = self.as_widget().rectangle.get();
+		let old = rect.size;
 		rect.resize(sz);
 		self.as_widget().rectangle.set(rect);
 		self.resized(*sz);
+		self.as_widget().notify_resized(old);
 
 		let det = self.det();
 		let det = det.as_ref().expect("det").borrow();
@@ -114,10 +94,83 @@ impl MainWindow
 			widget: WidgetBase::named("MainWindow"),
 			title: RefCell::new(title.to_string()),
 			child_widgets: RefCell::new(vec!()),
+			close_requested_callbacks: RefCell::new(vec!()),
 		};
 		w
 	}
 
+	/// call `cb` when the window manager asks to close the window;
+	/// if any callback returns false the window stays open
+	pub fn on_close_requested<F: 'static+FnMut() -> bool>(&self, cb: F)
+	{
+		self.close_requested_callbacks.borrow_mut().push(Box::new(cb));
+	}
+
+	/// destroy the window; `show` makes a new one
+	pub fn close(&self)
+	{
+		let window = self.widget.true_window_id();
+		if window == 0 { return; }
+		if let Some(det) = self.det()
+		{
+			det.borrow().destroy_window(window);
+		}
+		self.widget.true_window_id.set(0);
+	}
+
+	/// unmap the window, keeping it for `show`
+	pub fn hide(&self)
+	{
+		let window = self.widget.true_window_id();
+		if window == 0 { return; }
+		if let Some(det) = self.det()
+		{
+			let det = det.borrow();
+			xcb::unmap_window(&det.connection, window);
+			det.connection.flush();
+		}
+	}
+
+	/// map the window, making a new one if it was closed;
+	/// panics if the window can't be made
+	pub fn show(&self)
+	{
+		self.try_show().unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// like `show`, but returns an error if the
+	/// window can't be made
+	pub fn try_show(&self) -> Result<(), Error>
+	{
+		let det = match self.det()
+		{
+			Some(det) => det,
+			None => return Ok(()),
+		};
+
+		let window = self.widget.true_window_id();
+		if window != 0
+		{
+			let det = det.borrow();
+			xcb::map_window(&det.connection, window);
+			det.connection.flush();
+			return Ok(());
+		}
+
+		let this = match self.widget.this()
+		{
+			Some(this) => this,
+			None => return Ok(()),
+		};
+		let window = det.borrow().make_real_window()?;
+		self.widget.true_window_id.set(window);
+		det.borrow().add_top_level(this);
+		self.setup_title();
+		let sz = self.widget.rectangle.get().size;
+		self.resize(&sz);
+		Ok(())
+	}
+
 	pub fn set_title(&self, title: String)
 	{
 		self.title.replace(title);
@@ -166,6 +219,8 @@ impl MainWindow
 		}
 
 		let b = Rc::new(widget);
+		register_widget(&b);
+		set_parent(b.as_widget(), &self.widget);
 
 		self.child_widgets.borrow_mut().push( b.clone() );
 		b
@@ -226,3 +281,10 @@ impl MainWindow
 	}
 }
 
+impl Drop for MainWindow
+{
+	fn drop(&mut self)
+	{
+		self.close();
+	}
+}
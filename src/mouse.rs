@@ -0,0 +1,133 @@
+use crate::keyboard::Modifiers;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseButton
+{
+	Left,
+	Middle,
+	Right,
+	Back,
+	Forward,
+	Other(u8),
+}
+
+impl MouseButton
+{
+	/// the button for an X button number, which
+	/// must not be one of the wheel buttons 4 to 7
+	pub(crate) fn from_x(detail: u8) -> MouseButton
+	{
+		match detail
+		{
+			1 => MouseButton::Left,
+			2 => MouseButton::Middle,
+			3 => MouseButton::Right,
+			8 => MouseButton::Back,
+			9 => MouseButton::Forward,
+			n => MouseButton::Other(n),
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MouseAction
+{
+	Press(MouseButton),
+	Release(MouseButton),
+	Motion,
+	/// a scroll wheel step; negative `dy` scrolls up
+	/// and negative `dx` scrolls left
+	Wheel { dx : i32, dy : i32 },
+}
+
+/// The mouse buttons held down at the time of an event
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct MouseButtons
+{
+	pub left : bool,
+	pub middle : bool,
+	pub right : bool,
+}
+
+impl MouseButtons
+{
+	pub fn any(&self) -> bool
+	{
+		self.left || self.middle || self.right
+	}
+
+	pub fn without(&self, button: MouseButton) -> MouseButtons
+	{
+		let mut b = *self;
+		match button
+		{
+			MouseButton::Left => b.left = false,
+			MouseButton::Middle => b.middle = false,
+			MouseButton::Right => b.right = false,
+			_ => {},
+		}
+		b
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MouseEvent
+{
+	pub action : MouseAction,
+	pub modifiers : Modifiers,
+	pub buttons : MouseButtons,
+	/// the X server time of the event, in milliseconds
+	pub time : u32,
+	/// for presses and releases, 1 for a single click, 2 for
+	/// a double click and so on; 0 for other events
+	pub click_count : u32,
+}
+
+impl MouseEvent
+{
+	pub(crate) fn new(action: MouseAction, state: u16, time: u32) -> MouseEvent
+	{
+		let state = state as u32;
+		MouseEvent
+		{
+			action,
+			modifiers : Modifiers::from_x_state(state as u16),
+			buttons : MouseButtons
+			{
+				left : state & xcb::KEY_BUT_MASK_BUTTON_1 != 0,
+				middle : state & xcb::KEY_BUT_MASK_BUTTON_2 != 0,
+				right : state & xcb::KEY_BUT_MASK_BUTTON_3 != 0,
+			},
+			time,
+			click_count : 0,
+		}
+	}
+
+	/// the action for an X button press or release
+	pub(crate) fn button_action(press: bool, detail: u8) -> Option<MouseAction>
+	{
+		let wheel = |dx, dy| if press { Some(MouseAction::Wheel { dx, dy }) } else { None };
+		match detail
+		{
+			4 => wheel(0, -1),
+			5 => wheel(0, 1),
+			6 => wheel(-1, 0),
+			7 => wheel(1, 0),
+			b if press => Some(MouseAction::Press(MouseButton::from_x(b))),
+			b => Some(MouseAction::Release(MouseButton::from_x(b))),
+		}
+	}
+
+	pub fn is_press(&self, button: MouseButton) -> bool
+	{
+		self.action == MouseAction::Press(button)
+	}
+	pub fn is_release(&self, button: MouseButton) -> bool
+	{
+		self.action == MouseAction::Release(button)
+	}
+	pub fn is_motion(&self) -> bool
+	{
+		self.action == MouseAction::Motion
+	}
+}
@@ -5,9 +5,11 @@ pub struct PushButton
 {
 	widget: WidgetBase,
 	is_pressed: Cell<bool>,
+	mouse_down: Cell<bool>,
 	is_toggled: Cell<bool>,
 	text: RefCell<String>,
-	clicked_callbacks: RefCell<Vec<Box<FnMut()>>>,
+	clicked: Signal<()>,
+	toggled: Signal<bool>,
 	font_size: Cell<f64>,
 }
 
@@ -21,16 +23,68 @@ impl Widget for PushButton
 	{
 		&mut self.widget
 	}
-	fn mouse_event(&self, e: MouseEvent, _pos: &Point)
+	fn mouse_event(&self, e: MouseEvent, pos: &Point)
 	{
-		eprintln!("MOUSE {} {}", self.name(), self.text.borrow());
-		if e == MouseEvent::LeftPress
+		// a click happens when the button is released over us,
+		// sliding off before releasing cancels it
+		let inside = Rectangle::coords(0, 0, self.width(), self.height())
+			.contains(pos);
+
+		if e.is_press(MouseButton::Left)
+		{
+			self.mouse_down.set(true);
+			self.is_pressed.set(true);
+		}
+		else if e.is_motion() && self.mouse_down.get()
+		{
+			if self.is_pressed.get() == inside { return; }
+			self.is_pressed.set(inside);
+		}
+		else if e.is_release(MouseButton::Left) && self.mouse_down.get()
+		{
+			self.mouse_down.set(false);
+			self.is_pressed.set(false);
+			if inside
+			{
+				self.clicked.emit(&());
+			}
+		}
+		else
+		{
+			return;
+		}
+		self.repaint();
+	}
+
+	fn enter_event(&self)
+	{
+		self.repaint();
+	}
+	fn leave_event(&self)
+	{
+		self.repaint();
+	}
+
+	fn key_event(&self, e: &KeyEvent) -> bool
+	{
+		if e.keysym != keysym::SPACE && e.keysym != keysym::RETURN
+			&& e.keysym != keysym::KP_ENTER
+		{
+			return false;
+		}
+		if e.autorepeat { return true; }
+
+		if e.is_press()
+		{
+			self.is_pressed.set(true);
+		}
+		else if self.is_pressed.get()
 		{
-			for c in self.clicked_callbacks.borrow_mut().iter_mut()
-				{ c(); }
+			self.is_pressed.set(false);
+			self.clicked.emit(&());
 		}
-		self.is_pressed.set( e == MouseEvent::LeftPress );
 		self.repaint();
+		true
 	}
 
 	fn draw(&self, draw: &mut cairo::Cairo)
@@ -250,8 +304,11 @@ impl Widget for PushButton
 		}
 		else
 		{
-			let graya = Color::rgb(0xde, 0xdc, 0xd8);
-			let grayb = Color::rgb(0xc9, 0xc4, 0xbe);
+			let (graya, grayb) =
+				if self.is_hovered()
+					{ (Color::rgb(0xe8, 0xe6, 0xe3), Color::rgb(0xd6, 0xd2, 0xcd)) }
+				else
+					{ (Color::rgb(0xde, 0xdc, 0xd8), Color::rgb(0xc9, 0xc4, 0xbe)) };
 			draw.vgradient(2, height-4, graya, grayb);
 
 			draw.set_antialias(cairo::antialias::Antialias::None);
@@ -474,6 +531,11 @@ impl Widget for PushButton
 			(height as f64+extents.height)/2.0 as f64
 		);
 		draw.show_text(&self.text.borrow());
+
+		if self.has_focus()
+		{
+			draw.focus_rect(3, 3, width-6, height-6);
+		}
 	}
 }
 
@@ -487,12 +549,15 @@ impl PushButton
 			widget: WidgetBase::named("PushButton"),
 			text: RefCell::new(text),
 			is_pressed: Cell::new(false),
+			mouse_down: Cell::new(false),
 			is_toggled: Cell::new(false),
-			clicked_callbacks: RefCell::new(vec!()),
+			clicked: Signal::new(),
+			toggled: Signal::new(),
 			font_size: Cell::new(20.0),
 		};
 
 		w.widget.set_maximum_size(Size{ width:u32::max_value(), height:22 });
+		w.set_focusable(true);
 		w
 	}
 
@@ -505,16 +570,36 @@ impl PushButton
 		self.text.borrow().clone()
 	}
 
-	pub fn on_click<F>(&self, cb : F)
+	/// emitted when the button is clicked
+	pub fn clicked(&self) -> &Signal<()>
+	{
+		&self.clicked
+	}
+	/// emitted with the new state when the button is toggled
+	pub fn toggled(&self) -> &Signal<bool>
+	{
+		&self.toggled
+	}
+
+	/// call `cb` whenever the button is clicked
+	pub fn on_click<F>(&self, mut cb : F)
 		where F: FnMut() + 'static
 	{
-		self.clicked_callbacks.borrow_mut().push(Box::new(cb));
+		self.clicked.connect(move |_| cb()).detach();
 	}
 
 	pub fn set_toggled(&self, pressed: bool)
 	{
-		self.is_toggled.set(pressed);
+		let changed = self.is_toggled.replace(pressed) != pressed;
 		self.repaint();
+		if changed
+		{
+			self.toggled.emit(&pressed);
+		}
+	}
+	pub fn is_toggled(&self) -> bool
+	{
+		self.is_toggled.get()
 	}
 	pub fn set_font_size(&self, points: f64)
 	{
@@ -1,48 +1,170 @@
 use antidote::RwLock;
 
 use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::rc::Rc;
+use std::cell::{RefCell, Cell};
+use std::os::unix::io::RawFd;
+use libc::c_int;
+
+/// What to wait for on a watched file descriptor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FdInterest
+{
+	pub readable : bool,
+	pub writable : bool,
+}
+
+impl FdInterest
+{
+	pub fn read() -> FdInterest
+	{
+		FdInterest { readable: true, writable: false }
+	}
+	pub fn write() -> FdInterest
+	{
+		FdInterest { readable: false, writable: true }
+	}
+	pub fn read_write() -> FdInterest
+	{
+		FdInterest { readable: true, writable: true }
+	}
+}
+
+/// What a watched file descriptor is ready for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FdReady
+{
+	/// reading will not block; also set when the other end hung up
+	pub readable : bool,
+	pub writable : bool,
+	/// an error condition, or the fd is not open
+	pub error : bool,
+}
+
+/// A handle to a file descriptor watched with `watch_fd`.
+///
+/// Dropping the handle does not remove the watch, call `remove`.
+#[derive(Clone)]
+pub struct FdWatch
+{
+	active: Rc<Cell<bool>>,
+}
+
+impl FdWatch
+{
+	/// stop watching; the callback will not be called again
+	pub fn remove(&self)
+	{
+		self.active.set(false);
+	}
+
+	pub fn is_active(&self) -> bool
+	{
+		self.active.get()
+	}
+}
+
+struct FdWatchEntry
+{
+	fd: RawFd,
+	interest: FdInterest,
+	active: Rc<Cell<bool>>,
+	callback: RefCell<Box<FnMut(FdReady)>>,
+}
 
 pub(crate) struct EventPoster
 {
 	xcb_fd: std::os::unix::io::RawFd,
 	event_r_fd: std::os::unix::io::RawFd,
 	event_w_fd: std::os::unix::io::RawFd,
-	pub(crate) receivers: RwLock<Vec<Box<FnMut()>>>,
+	/// return false once their channel is finished
+	pub(crate) receivers: RwLock<Vec<Box<FnMut() -> bool>>>,
+	watches: RefCell<Vec<Rc<FdWatchEntry>>>,
 }
 
 impl EventPoster
 {
 	pub(crate) fn new(xcb: &xcb::base::Connection)
-		-> EventPoster
+		-> crate::error::Result<EventPoster>
 	{
 		let xcb_fd = unsafe { xcb::ffi::base::xcb_get_file_descriptor(xcb.get_raw_conn()) };
+		EventPoster::with_fd(xcb_fd)
+	}
 
-		let (event_r_fd, event_w_fd) =
-			nix::unistd::pipe().expect("pipe");
+	/// wait on `xcb_fd` for X events
+	fn with_fd(xcb_fd: RawFd) -> crate::error::Result<EventPoster>
+	{
+		let (event_r_fd, event_w_fd) = nix::unistd::pipe()?;
 		nix::fcntl::fcntl(
 			event_r_fd,
 			nix::fcntl::FcntlArg::F_SETFL(
 				nix::fcntl::OFlag::O_NONBLOCK
 			)
-		).expect("fnctl nonblock on event notifier");
-		EventPoster
+		)?;
+		// a full pipe already wakes the event loop, so
+		// writers must not block on it
+		nix::fcntl::fcntl(
+			event_w_fd,
+			nix::fcntl::FcntlArg::F_SETFL(
+				nix::fcntl::OFlag::O_NONBLOCK
+			)
+		)?;
+		Ok(EventPoster
 		{
 			xcb_fd,
 			event_r_fd,
 			event_w_fd,
 			receivers: RwLock::new(vec!()),
-		}
+			watches: RefCell::new(vec!()),
+		})
 	}
 
-	pub(crate) fn wait(&self) -> bool
+	/// wait for X events, posted events or watched file descriptors,
+	/// but no longer than `timeout` if there is one. The callbacks
+	/// of ready watched file descriptors are called before returning.
+	pub(crate) fn wait(&self, timeout: Option<std::time::Duration>) -> bool
 	{
-		use nix::sys::select::*;
-		let mut rr = FdSet::new();
-		rr.insert(self.event_r_fd);
-		rr.insert(self.xcb_fd);
-		let _ = select(None, Some(&mut rr), None, None, None);
+		use nix::poll::*;
+
+		let watches : Vec<Rc<FdWatchEntry>>;
+		{
+			let mut w = self.watches.borrow_mut();
+			w.retain(|w| w.active.get());
+			watches = w.clone();
+		}
+
+		let mut fds = vec!(
+			PollFd::new(self.event_r_fd, EventFlags::POLLIN),
+			PollFd::new(self.xcb_fd, EventFlags::POLLIN),
+		);
+		for w in &watches
+		{
+			let mut events = EventFlags::empty();
+			if w.interest.readable { events |= EventFlags::POLLIN; }
+			if w.interest.writable { events |= EventFlags::POLLOUT; }
+			fds.push(PollFd::new(w.fd, events));
+		}
+
+		// round up, so a timer isn't woken for just before it is due
+		let timeout = timeout.map(
+			|t|
+			{
+				let ms = t.as_secs() * 1000 + (t.subsec_nanos() as u64 + 999_999) / 1_000_000;
+				std::cmp::min(ms, c_int::max_value() as u64) as c_int
+			}
+		).unwrap_or(-1);
+
+		if poll(&mut fds, timeout).is_err()
+		{
+			// interrupted, nothing is ready
+			return false;
+		}
+
+		let revents = |fd: &PollFd| fd.revents().unwrap_or(EventFlags::empty());
 
-		let c = rr.contains(self.event_r_fd);
+		let c = revents(&fds[0]).contains(EventFlags::POLLIN);
 		loop
 		{
 			let mut trash = [0u8; 4096];
@@ -56,35 +178,140 @@ impl EventPoster
 				break;
 			}
 		}
+
+		for (w, fd) in watches.iter().zip(&fds[2..])
+		{
+			let r = revents(fd);
+			if r.is_empty() || !w.active.get() { continue; }
+
+			let hangup = r.intersects(EventFlags::POLLHUP);
+			let ready = FdReady
+			{
+				// a hang up is reported as readable, so the
+				// callback reads the end of file
+				readable: w.interest.readable
+					&& (r.contains(EventFlags::POLLIN) || hangup),
+				writable: w.interest.writable && r.contains(EventFlags::POLLOUT),
+				error: r.intersects(EventFlags::POLLERR | EventFlags::POLLNVAL),
+			};
+			if r.contains(EventFlags::POLLNVAL)
+			{
+				// the fd was closed without removing the watch
+				// and would otherwise be reported forever
+				w.active.set(false);
+			}
+			(w.callback.borrow_mut())(ready);
+		}
 		c
 	}
 
+	pub(crate) fn watch_fd(
+		&self,
+		fd: RawFd,
+		interest: FdInterest,
+		callback: Box<FnMut(FdReady)>,
+	) -> FdWatch
+	{
+		let active = Rc::new(Cell::new(true));
+		self.watches.borrow_mut().push(
+			Rc::new(
+				FdWatchEntry
+				{
+					fd,
+					interest,
+					active: active.clone(),
+					callback: RefCell::new(callback),
+				}
+			)
+		);
+		FdWatch { active }
+	}
+
+	/// the fd that wakes `wait` when written to
+	pub(crate) fn notify_fd(&self) -> RawFd
+	{
+		self.event_w_fd
+	}
+
 	pub(crate) fn process_channels(&self)
 	{
+		// handlers may make new channels, so don't
+		// hold the lock while they run
+		let current = std::mem::replace(&mut *self.receivers.write(), vec!());
+		let mut current : Vec<_> = current
+			.into_iter()
+			.filter_map(|mut c| if c() { Some(c) } else { None })
+			.collect();
+
 		let mut e = self.receivers.write();
-		for c in &mut *e
-		{
-			c();
-		}
+		current.extend(e.drain(..));
+		*e = current;
 	}
 
-	pub(crate) fn channel<T, F>(
+	pub(crate) fn channel<T, F, R>(
 		&self,
-		mut f: F,
+		f: F,
 	) -> ChannelWrite<T>
 	where
-		T: 'static+Clone+Sized+Send,
-		F: 'static+FnMut(T)
+		T: 'static+Sized+Send,
+		F: 'static+FnMut(T) -> R,
+		R: HandlerResult
 	{
 		let (sr, rr) = mpsc::channel();
+		self.add_receiver(rr, f, Sender::Unbounded(sr))
+	}
+
+	/// panics if `capacity` is 0, as a sender would wait for
+	/// the handler before the event loop knows to run it
+	pub(crate) fn bounded_channel<T, F, R>(
+		&self,
+		capacity: usize,
+		f: F,
+	) -> ChannelWrite<T>
+	where
+		T: 'static+Sized+Send,
+		F: 'static+FnMut(T) -> R,
+		R: HandlerResult
+	{
+		assert!(capacity > 0, "a bounded channel needs a capacity of at least 1");
+		let (sr, rr) = mpsc::sync_channel(capacity);
+		self.add_receiver(rr, f, Sender::Bounded(sr))
+	}
+
+	fn add_receiver<T, F, R>(
+		&self,
+		rr: mpsc::Receiver<T>,
+		mut f: F,
+		sender: Sender<T>,
+	) -> ChannelWrite<T>
+	where
+		T: 'static+Sized+Send,
+		F: 'static+FnMut(T) -> R,
+		R: HandlerResult
+	{
+		let closed = Arc::new(AtomicBool::new(false));
+		let c = closed.clone();
 		let mut e = self.receivers.write();
 		e.push(
 			Box::new(
 				move ||
 				{
-					while let Ok(o) = rr.try_recv()
+					// returning false drops the receiver,
+					// so sending fails from then on
+					loop
 					{
-						f(o);
+						if c.load(Ordering::SeqCst) { return false; }
+						match rr.try_recv()
+						{
+							Ok(o) =>
+								if !f(o).keep_open()
+								{
+									c.store(true, Ordering::SeqCst);
+									return false;
+								},
+							Err(mpsc::TryRecvError::Empty) => return true,
+							Err(mpsc::TryRecvError::Disconnected) => return false,
+						}
 					}
 				}
 			)
@@ -92,23 +319,310 @@ impl EventPoster
 		ChannelWrite
 		{
 			event_w_fd: self.event_w_fd,
-			sender: sr,
+			sender,
+			closed,
 		}
 	}
 }
 
+/// What a channel's handler returns: either nothing,
+/// or `false` to close the channel
+pub trait HandlerResult
+{
+	fn keep_open(self) -> bool;
+}
+
+impl HandlerResult for ()
+{
+	fn keep_open(self) -> bool
+	{
+		true
+	}
+}
+
+impl HandlerResult for bool
+{
+	fn keep_open(self) -> bool
+	{
+		self
+	}
+}
+
+enum Sender<T>
+{
+	Unbounded(mpsc::Sender<T>),
+	Bounded(mpsc::SyncSender<T>),
+}
 
+impl<T> Clone for Sender<T>
+{
+	fn clone(&self) -> Sender<T>
+	{
+		match *self
+		{
+			Sender::Unbounded(ref s) => Sender::Unbounded(s.clone()),
+			Sender::Bounded(ref s) => Sender::Bounded(s.clone()),
+		}
+	}
+}
+
+/// Sends values to a handler on the GUI thread; made by
+/// `Graphical::channel` and `Graphical::bounded_channel`.
+///
+/// The handler is removed when every `ChannelWrite` is dropped,
+/// the channel is closed, or the handler returns `false`.
 pub struct ChannelWrite<T>
 {
 	event_w_fd: std::os::unix::io::RawFd,
-	sender: mpsc::Sender<T>,
+	sender: Sender<T>,
+	closed: Arc<AtomicBool>,
+}
+
+impl<T> Clone for ChannelWrite<T>
+{
+	fn clone(&self) -> ChannelWrite<T>
+	{
+		ChannelWrite
+		{
+			event_w_fd: self.event_w_fd,
+			sender: self.sender.clone(),
+			closed: self.closed.clone(),
+		}
+	}
 }
 
 impl<T> ChannelWrite<T>
 {
-	pub fn send(&self, obj: T)
+	/// send `obj` to the GUI thread, waiting for room if the channel
+	/// is bounded and full; fails if the channel is closed.
+	///
+	/// Waiting for room on the GUI thread itself never ends,
+	/// use `try_send` there.
+	pub fn send(&self, obj: T) -> Result<(), mpsc::SendError<T>>
+	{
+		if self.is_closed()
+		{
+			return Err(mpsc::SendError(obj));
+		}
+		match self.sender
+		{
+			Sender::Unbounded(ref s) => s.send(obj)?,
+			Sender::Bounded(ref s) =>
+				match s.try_send(obj)
+				{
+					Ok(()) => {},
+					Err(mpsc::TrySendError::Full(obj)) =>
+					{
+						// make sure the GUI thread is
+						// emptying it before waiting
+						self.notify();
+						s.send(obj)?;
+					},
+					Err(mpsc::TrySendError::Disconnected(obj)) =>
+						return Err(mpsc::SendError(obj)),
+				},
+		}
+		self.notify();
+		Ok(())
+	}
+
+	/// send `obj` to the GUI thread if there is room for it
+	/// without waiting
+	pub fn try_send(&self, obj: T) -> Result<(), mpsc::TrySendError<T>>
+	{
+		if self.is_closed()
+		{
+			return Err(mpsc::TrySendError::Disconnected(obj));
+		}
+		match self.sender
+		{
+			Sender::Unbounded(ref s) =>
+				s.send(obj).map_err(|e| mpsc::TrySendError::Disconnected(e.0))?,
+			Sender::Bounded(ref s) => s.try_send(obj)?,
+		}
+		self.notify();
+		Ok(())
+	}
+
+	/// stop the handler; values that haven't been handled
+	/// yet are dropped, and sending fails from now on
+	pub fn close(&self)
+	{
+		self.closed.store(true, Ordering::SeqCst);
+		self.notify();
+	}
+
+	pub fn is_closed(&self) -> bool
+	{
+		self.closed.load(Ordering::SeqCst)
+	}
+
+	fn notify(&self)
 	{
-		self.sender.send(obj).unwrap();
 		let _ = nix::unistd::write(self.event_w_fd, b"1");
 	}
 }
+
+impl<T> Drop for ChannelWrite<T>
+{
+	fn drop(&mut self)
+	{
+		// if this was the last sender, the
+		// handler can now be removed
+		self.notify();
+	}
+}
+
+
+/// Runs closures on the GUI thread from any thread;
+/// made by `Graphical::invoker`
+#[derive(Clone)]
+pub struct GuiInvoker
+{
+	channel: ChannelWrite<Box<FnOnce() + Send>>,
+	gui_thread: std::thread::ThreadId,
+}
+
+impl GuiInvoker
+{
+	pub(crate) fn new(poster: &EventPoster) -> GuiInvoker
+	{
+		GuiInvoker
+		{
+			channel: poster.channel(|f: Box<FnOnce() + Send>| f()),
+			gui_thread: std::thread::current().id(),
+		}
+	}
+
+	/// run `f` on the GUI thread without waiting for it;
+	/// fails if the event loop is gone
+	pub fn invoke<F>(&self, f: F) -> Result<(), crate::error::Error>
+	where F: 'static+FnOnce()+Send
+	{
+		self.channel.send(Box::new(f))
+			.map_err(|_| crate::error::Error::Disconnected)
+	}
+
+	/// run `f` on the GUI thread and wait for its result; fails if the
+	/// event loop is gone before `f` runs. On the GUI thread itself,
+	/// `f` is run at once.
+	pub fn invoke_blocking<R, F>(&self, f: F) -> Result<R, crate::error::Error>
+	where
+		R: 'static+Send,
+		F: 'static+FnOnce() -> R+Send
+	{
+		if std::thread::current().id() == self.gui_thread
+		{
+			return Ok(f());
+		}
+
+		let (sr, rr) = mpsc::channel();
+		self.invoke(
+			move ||
+			{
+				let _ = sr.send(f());
+			}
+		)?;
+		// the sender is dropped without sending if
+		// the closure is dropped without being run
+		rr.recv().map_err(|_| crate::error::Error::Disconnected)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::time::Duration;
+
+	fn poster() -> EventPoster
+	{
+		// stands in for the X connection, which never has events
+		let (x_r, _x_w) = nix::unistd::pipe().unwrap();
+		EventPoster::with_fd(x_r).unwrap()
+	}
+
+	#[test]
+	fn bounded_channel_holds_capacity()
+	{
+		let p = poster();
+		let got = Rc::new(RefCell::new(vec!()));
+		let g = got.clone();
+		let w = p.bounded_channel(2, move |v: i32| g.borrow_mut().push(v));
+
+		w.try_send(1).unwrap();
+		w.try_send(2).unwrap();
+		match w.try_send(3)
+		{
+			Err(mpsc::TrySendError::Full(3)) => {},
+			_ => panic!("the channel should be full"),
+		}
+
+		p.process_channels();
+		assert_eq!(*got.borrow(), vec!(1, 2));
+		w.try_send(3).unwrap();
+		p.process_channels();
+		assert_eq!(*got.borrow(), vec!(1, 2, 3));
+	}
+
+	#[test]
+	fn full_bounded_channel_send_wakes_gui()
+	{
+		let p = poster();
+		let got = Rc::new(RefCell::new(vec!()));
+		let g = got.clone();
+		let w = p.bounded_channel(1, move |v: i32| g.borrow_mut().push(v));
+
+		let sender = std::thread::spawn(
+			move ||
+			{
+				for i in 0..5
+					{ w.send(i).unwrap(); }
+			}
+		);
+
+		// the GUI thread is woken for every value, rather
+		// than only handling them when it times out
+		let start = std::time::Instant::now();
+		while got.borrow().len() < 5
+		{
+			p.wait(Some(Duration::from_secs(5)));
+			p.process_channels();
+		}
+		assert!(start.elapsed() < Duration::from_secs(2));
+		sender.join().unwrap();
+		assert_eq!(*got.borrow(), vec!(0, 1, 2, 3, 4));
+	}
+
+	#[test]
+	fn handler_closes_channel()
+	{
+		let p = poster();
+		let got = Rc::new(RefCell::new(vec!()));
+		let g = got.clone();
+		let w = p.channel(
+			move |v: i32|
+			{
+				g.borrow_mut().push(v);
+				v != 2
+			}
+		);
+
+		w.send(1).unwrap();
+		w.send(2).unwrap();
+		w.send(3).unwrap();
+		p.process_channels();
+		assert_eq!(*got.borrow(), vec!(1, 2));
+		assert!(w.is_closed());
+		assert!(w.send(4).is_err());
+		assert!(p.receivers.read().is_empty());
+	}
+
+	#[test]
+	#[should_panic]
+	fn bounded_channel_needs_capacity()
+	{
+		let p = poster();
+		p.bounded_channel(0, |_: i32| {});
+	}
+}
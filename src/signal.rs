@@ -0,0 +1,278 @@
+use std::rc::Rc;
+use std::cell::{RefCell, Cell};
+
+struct Slot<T>
+{
+	connected: Rc<Cell<bool>>,
+	/// returns false once it should be disconnected
+	f: Rc<RefCell<Box<FnMut(&T) -> bool>>>,
+}
+
+/// A list of callbacks that are all called with a value
+/// when something happens, such as a button being clicked
+pub struct Signal<T>
+{
+	slots: RefCell<Vec<Slot<T>>>,
+}
+
+impl<T> Signal<T>
+{
+	pub fn new() -> Signal<T>
+	{
+		Signal
+		{
+			slots: RefCell::new(vec!()),
+		}
+	}
+
+	/// call `f` every time the signal is emitted, until
+	/// the returned `Connection` is dropped
+	pub fn connect<F>(&self, mut f: F) -> Connection
+		where F: FnMut(&T) + 'static
+	{
+		self.add_slot(Box::new(move |v| { f(v); true }))
+	}
+
+	/// call `f` with `receiver` every time the signal is emitted,
+	/// without keeping `receiver` alive; the connection ends
+	/// when `receiver` is dropped
+	pub fn connect_weak<R, F>(&self, receiver: &Rc<R>, mut f: F) -> Connection
+		where R: ?Sized + 'static, F: FnMut(&R, &T) + 'static
+	{
+		let receiver = Rc::downgrade(receiver);
+		self.add_slot(
+			Box::new(
+				move |v|
+				{
+					match receiver.upgrade()
+					{
+						Some(r) => { f(&r, v); true },
+						None => false,
+					}
+				}
+			)
+		)
+	}
+
+	fn add_slot(&self, f: Box<FnMut(&T) -> bool>) -> Connection
+	{
+		let connected = Rc::new(Cell::new(true));
+		self.slots.borrow_mut().push(
+			Slot
+			{
+				connected: connected.clone(),
+				f: Rc::new(RefCell::new(f)),
+			}
+		);
+		Connection { connected, detached: false }
+	}
+
+	/// call every connected callback with `value`
+	pub fn emit(&self, value: &T)
+	{
+		// callbacks may connect and disconnect while
+		// this runs, so call a copy of the list
+		let slots : Vec<_> = self.slots.borrow()
+			.iter()
+			.map(|s| (s.connected.clone(), s.f.clone()))
+			.collect();
+
+		for (connected, f) in slots
+		{
+			if !connected.get() { continue; }
+			// a callback that emits this signal again
+			// isn't called recursively
+			if let Ok(mut f) = f.try_borrow_mut()
+			{
+				if !f(value)
+					{ connected.set(false); }
+			}
+		}
+
+		self.slots.borrow_mut().retain(|s| s.connected.get());
+	}
+
+	/// true if any callbacks are connected
+	pub fn is_connected(&self) -> bool
+	{
+		self.slots.borrow().iter().any(|s| s.connected.get())
+	}
+}
+
+impl<T> Default for Signal<T>
+{
+	fn default() -> Signal<T>
+	{
+		Signal::new()
+	}
+}
+
+/// A callback connected to a `Signal`, which is
+/// disconnected when this is dropped
+#[must_use = "dropping a Connection disconnects it at once; call `detach` to keep it"]
+pub struct Connection
+{
+	connected: Rc<Cell<bool>>,
+	detached: bool,
+}
+
+impl Connection
+{
+	pub fn disconnect(self)
+	{
+		self.connected.set(false);
+	}
+
+	/// keep the callback connected for as long as the signal exists
+	pub fn detach(mut self)
+	{
+		self.detached = true;
+	}
+
+	pub fn is_connected(&self) -> bool
+	{
+		self.connected.get()
+	}
+}
+
+impl Drop for Connection
+{
+	fn drop(&mut self)
+	{
+		if !self.detached
+		{
+			self.connected.set(false);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn recorder() -> (Rc<RefCell<Vec<i32>>>, impl FnMut(&i32))
+	{
+		let got = Rc::new(RefCell::new(vec!()));
+		let g = got.clone();
+		(got, move |v: &i32| g.borrow_mut().push(*v))
+	}
+
+	#[test]
+	fn drop_disconnects()
+	{
+		let s = Signal::new();
+		let (got, f) = recorder();
+		let c = s.connect(f);
+		s.emit(&1);
+		drop(c);
+		s.emit(&2);
+		assert_eq!(*got.borrow(), vec!(1));
+		assert!(!s.is_connected());
+	}
+
+	#[test]
+	fn disconnect()
+	{
+		let s = Signal::new();
+		let (got, f) = recorder();
+		let c = s.connect(f);
+		assert!(c.is_connected());
+		c.disconnect();
+		s.emit(&1);
+		assert!(got.borrow().is_empty());
+	}
+
+	#[test]
+	fn detach_keeps_connection()
+	{
+		let s = Signal::new();
+		let (got, f) = recorder();
+		s.connect(f).detach();
+		s.emit(&1);
+		s.emit(&2);
+		assert_eq!(*got.borrow(), vec!(1, 2));
+		assert!(s.is_connected());
+	}
+
+	#[test]
+	fn dead_weak_receiver_disconnects()
+	{
+		let s = Signal::new();
+		let receiver = Rc::new(RefCell::new(vec!()));
+		let c = s.connect_weak(&receiver, |r: &RefCell<Vec<i32>>, v: &i32| r.borrow_mut().push(*v));
+		s.emit(&1);
+		assert_eq!(*receiver.borrow(), vec!(1));
+
+		drop(receiver);
+		s.emit(&2);
+		assert!(!c.is_connected());
+		assert!(!s.is_connected());
+	}
+
+	#[test]
+	fn emit_from_callback_isnt_recursive()
+	{
+		let s = Rc::new(Signal::new());
+		let (got, mut f) = recorder();
+		let s2 = s.clone();
+		s.connect(
+			move |v: &i32|
+			{
+				f(v);
+				if *v == 1 { s2.emit(&2); }
+			}
+		).detach();
+		let (others, g) = recorder();
+		s.connect(g).detach();
+
+		s.emit(&1);
+		// the callback that emitted isn't called again,
+		// but the others see both values
+		assert_eq!(*got.borrow(), vec!(1));
+		assert_eq!(*others.borrow(), vec!(2, 1));
+	}
+
+	#[test]
+	fn connect_during_emit()
+	{
+		let s = Rc::new(Signal::new());
+		let (got, f) = recorder();
+		let f = RefCell::new(Some(f));
+		let s2 = s.clone();
+		s.connect(
+			move |_: &i32|
+			{
+				if let Some(f) = f.borrow_mut().take()
+					{ s2.connect(f).detach(); }
+			}
+		).detach();
+
+		// a callback connected while emitting is
+		// first called by the next emit
+		s.emit(&1);
+		assert!(got.borrow().is_empty());
+		s.emit(&2);
+		assert_eq!(*got.borrow(), vec!(2));
+	}
+
+	#[test]
+	fn disconnect_during_emit()
+	{
+		let s = Signal::new();
+		let (got, f) = recorder();
+		let later : Rc<RefCell<Option<Connection>>> = Rc::new(RefCell::new(None));
+		let l = later.clone();
+		s.connect(
+			move |_: &i32|
+			{
+				if let Some(c) = l.borrow_mut().take()
+					{ c.disconnect(); }
+			}
+		).detach();
+		*later.borrow_mut() = Some(s.connect(f));
+
+		s.emit(&1);
+		assert!(got.borrow().is_empty());
+	}
+}
@@ -7,6 +7,7 @@ pub struct TabBar
 	widget: WidgetBase,
 	buttons: RefCell<std::vec::Vec<(Rc<PushButton>)>>,
 	current_button: Cell<usize>,
+	current_changed: Signal<usize>,
 }
 
 impl Widget for TabBar
@@ -20,43 +21,29 @@ impl Widget for TabBar
 		&mut self.widget
 	}
 
-	fn setup_children(&self, det: Rc<RefCell<GraphicalDetails>>)
+	fn children(&self) -> Vec<Rc<Widget>>
 	{
-		for b in self.buttons.borrow_mut().iter_mut()
-		{
-			b.setup(det.clone());
-		}
+		self.buttons
+			.borrow()
+			.iter()
+			.map(|b| b.clone() as Rc<Widget>)
+			.collect()
 	}
 
 	fn draw(&self, c: &mut cairo::Cairo)
 	{
-		use std::borrow::Borrow;
+		draw_children(c, self);
 
-		for w in self.buttons.borrow().iter()
-		{
-			let wrect = w.rectangle();
-			c.save();
-			c.translate(wrect.x() as f64, wrect.y() as f64);
-			c.rectangle(0.0, 0.0, wrect.width() as f64, wrect.height() as f64);
-			c.clip();
-
-			let w = w.as_ref().borrow();
-			w.draw(c);
-			c.restore();
-		}
-	}
-
-	fn child_at(&self, pt: &Point) -> Option<Rc<Widget>>
-	{
-		for w in self.buttons.borrow().iter()
+		if self.has_focus()
 		{
-			if w.rectangle().contains(pt)
+			if let Some(b) = self.buttons.borrow().get(self.current_button.get())
 			{
-				return Some(w.clone());
+				let r = b.rectangle();
+				c.focus_rect(r.x()+3, r.y()+3, r.width() as i32-6, r.height() as i32-6);
 			}
 		}
-		None
 	}
+
 	fn resized(&self, _sz: Size)
 	{
 		let mut x=0;
@@ -71,32 +58,43 @@ impl Widget for TabBar
 	}
 	fn mouse_event(&self, e: MouseEvent, pos: &Point)
 	{
-		let c = self.current_button.get();
-		if e == MouseEvent::LeftPress
-		{
-			self.buttons.borrow()[c].set_toggled(false);
-		}
-
 		let b = self.child_at(pos);
 		if b.is_none() { return; }
 		let b = b.unwrap();
 
-		for (idx,w) in self.buttons.borrow().iter().enumerate()
+		if e.is_press(MouseButton::Left)
 		{
-			let w2: Rc<Widget> = w.clone();
-			if Rc::ptr_eq(&w2, &b)
+			let idx = self.buttons
+				.borrow()
+				.iter()
+				.position(
+					|w|
+					{
+						let w2: Rc<Widget> = w.clone();
+						Rc::ptr_eq(&w2, &b)
+					}
+				);
+			if let Some(idx) = idx
 			{
-				self.current_button.set(idx);
-				if e == MouseEvent::LeftPress
-				{
-					w.set_toggled(true);
-				}
-				break;
+				self.set_current(idx);
 			}
 		}
-		b.mouse_event(e, pos);
+		b.mouse_event(e, &b.map_from_parent(*pos));
+	}
 
-		self.repaint();
+	fn key_event(&self, e: &KeyEvent) -> bool
+	{
+		if !e.is_press() { return false; }
+
+		let c = self.current_button.get();
+		match e.keysym
+		{
+			keysym::LEFT if c > 0 => self.set_current(c-1),
+			keysym::RIGHT if c+1 < self.buttons.borrow().len()
+				=> self.set_current(c+1),
+			_ => return false,
+		}
+		true
 	}
 }
 
@@ -109,20 +107,26 @@ impl TabBar
 			widget: WidgetBase::named("TabBar"),
 			buttons: RefCell::new( vec!() ),
 			current_button: Cell::new(0),
+			current_changed: Signal::new(),
 		};
+		w.set_focusable(true);
 		w
 	}
 
 	pub fn add(&self, label: String) -> usize
 	{
 		let button = PushButton::new(label);
+		button.set_focusable(false);
 
 		let mut t = self.buttons.borrow_mut();
 		if let Some(c) = self.det()
 		{
 			button.setup(c);
 		}
-		t.push( Rc::new(button) );
+		let button = Rc::new(button);
+		register_widget(&button);
+		set_parent(button.as_widget(), &self.widget);
+		t.push( button );
 		t.len()-1
 	}
 
@@ -131,5 +135,35 @@ impl TabBar
 		self.current_button.get()
 	}
 
+	pub fn set_current(&self, idx: usize)
+	{
+		{
+			let buttons = self.buttons.borrow();
+			if idx >= buttons.len() { return; }
+			buttons[self.current_button.get()].set_toggled(false);
+			buttons[idx].set_toggled(true);
+		}
+		let changed = self.current_button.replace(idx) != idx;
+		self.repaint();
+
+		if changed
+		{
+			self.current_changed.emit(&idx);
+		}
+	}
+
+	/// emitted with the new index when the current tab changes
+	pub fn current_changed(&self) -> &Signal<usize>
+	{
+		&self.current_changed
+	}
+
+	/// call `cb` with the new index when the current tab changes
+	pub fn on_current_changed<F>(&self, mut cb: F)
+		where F: FnMut(usize) + 'static
+	{
+		self.current_changed.connect(move |&idx| cb(idx)).detach();
+	}
+
 }
 
@@ -6,7 +6,8 @@ pub struct TabWidget
 {
 	widget: WidgetBase,
 	tabbar: RefCell<Rc<TabBar>>,
-	tabs: RefCell<std::vec::Vec<Rc<Widget>>>,
+	tabs: Rc<RefCell<std::vec::Vec<Rc<Widget>>>>,
+	current_changed: Rc<Signal<usize>>,
 }
 
 impl Widget for TabWidget
@@ -20,52 +21,19 @@ impl Widget for TabWidget
 		&mut self.widget
 	}
 
-	fn setup_children(&self, det: Rc<RefCell<GraphicalDetails>>)
+	fn children(&self) -> Vec<Rc<Widget>>
 	{
-		self.tabbar.borrow().setup(det.clone());
-		for b in self.tabs.borrow().iter()
-		{
-			b.setup(det.clone());
-		}
+		let mut c : Vec<Rc<Widget>> = vec!(self.tabbar.borrow().clone());
+		c.extend(self.tabs.borrow().iter().cloned());
+		c
 	}
-
-	fn draw(&self, c: &mut cairo::Cairo)
+	fn visible_children(&self) -> Vec<Rc<Widget>>
 	{
-		use std::borrow::Borrow;
-
-		{
-			let w = self.tabbar.borrow();
-			let wrect = w.rectangle();
-			c.save();
-			c.translate(wrect.x() as f64, wrect.y() as f64);
-			c.rectangle(0.0, 0.0, wrect.width() as f64, wrect.height() as f64);
-			c.clip();
-
-			let w = w.borrow();
-			w.draw(c);
-			c.restore();
-		}
-		if let Some(w) = self.current_widget()
-		{
-			let wrect = w.rectangle();
-			c.save();
-			c.translate(wrect.x() as f64, wrect.y() as f64);
-			c.rectangle(0.0, 0.0, wrect.width() as f64, wrect.height() as f64);
-			c.clip();
-
-			let w = w.as_ref().borrow();
-			w.draw(c);
-			c.restore();
-		}
+		let mut c : Vec<Rc<Widget>> = vec!(self.tabbar.borrow().clone());
+		c.extend(self.current_widget());
+		c
 	}
 
-	fn child_at(&self, pt: &Point) -> Option<Rc<Widget>>
-	{
-		if self.tabbar.borrow().rectangle().contains(pt)
-			{ Some(self.tabbar.borrow().clone()) }
-		else
-			{ self.current_widget() }
-	}
 	fn resized(&self, sz: Size)
 	{
 		self.tabbar.borrow().resize( &sz.with_height(45).with_width(1000) );
@@ -79,29 +47,45 @@ impl Widget for TabWidget
 			);
 		}
 	}
-	fn mouse_event(&self, e: MouseEvent, pt: &Point)
-	{
-		if self.tabbar.borrow().rectangle().contains(pt)
-		{
-			self.tabbar.borrow().mouse_event(e, &self.tabbar.borrow().pt_from_parent(*pt));
-		}
-		else if let Some(current) = self.current_widget()
-		{
-			current.mouse_event(e, &current.pt_from_parent(*pt));
-		}
-	}
-
 }
 
 impl TabWidget
 {
 	pub fn new() -> TabWidget
 	{
+		let tabbar = Rc::new(TabBar::new());
+		register_widget(&tabbar);
+
+		let tabs : Rc<RefCell<Vec<Rc<Widget>>>> = Rc::new(RefCell::new( vec!() ));
+		let current_changed = Rc::new(Signal::new());
+		{
+			let tabs = Rc::downgrade(&tabs);
+			let current_changed = current_changed.clone();
+			tabbar.on_current_changed(
+				move |idx|
+				{
+					current_changed.emit(&idx);
+
+					let tabs = match tabs.upgrade()
+					{
+						Some(tabs) => tabs,
+						None => return,
+					};
+					let shown = tabs.borrow().get(idx).cloned();
+					if let Some(shown) = shown
+					{
+						shown.update();
+					}
+				}
+			);
+		}
+
 		let w = TabWidget
 		{
 			widget: WidgetBase::named("TabWidget"),
-			tabbar: RefCell::new( Rc::new(TabBar::new()) ),
-			tabs: RefCell::new( vec!() ),
+			tabbar: RefCell::new( tabbar ),
+			tabs,
+			current_changed,
 		};
 		w
 	}
@@ -114,11 +98,19 @@ impl TabWidget
 		if let Some(c) = self.det()
 			{ widget.setup(c); }
 		let b = Rc::new(widget);
+		register_widget(&b);
+		set_parent(b.as_widget(), &self.widget);
 		t.push( b.clone() );
 		self.tabbar.borrow().add(label);
 		b
 	}
 
+	/// emitted with the new index when the current tab changes
+	pub fn current_changed(&self) -> &Signal<usize>
+	{
+		&self.current_changed
+	}
+
 	fn has_tabs(&self) -> bool
 	{
 		self.tabs.borrow().len() != 0
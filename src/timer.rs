@@ -0,0 +1,227 @@
+use std::rc::Rc;
+use std::cell::{RefCell, Cell};
+use std::time::{Duration, Instant};
+
+/// A handle to a timer started with `single_shot` or `interval`.
+///
+/// Dropping the handle does not stop the timer, call `cancel`.
+#[derive(Clone)]
+pub struct Timer
+{
+	active: Rc<Cell<bool>>,
+}
+
+impl Timer
+{
+	/// stop the timer; its callback will not be called again
+	pub fn cancel(&self)
+	{
+		self.active.set(false);
+	}
+
+	/// true until a single shot timer has fired or the timer is cancelled
+	pub fn is_active(&self) -> bool
+	{
+		self.active.get()
+	}
+}
+
+struct TimerEntry
+{
+	deadline: Instant,
+	interval: Option<Duration>,
+	active: Rc<Cell<bool>>,
+	callback: Box<FnMut()>,
+}
+
+/// The timers of the event loop
+pub(crate) struct Timers
+{
+	entries: RefCell<Vec<TimerEntry>>,
+}
+
+impl Timers
+{
+	pub(crate) fn new() -> Timers
+	{
+		Timers
+		{
+			entries: RefCell::new(vec!()),
+		}
+	}
+
+	pub(crate) fn add(
+		&self,
+		delay: Duration,
+		interval: Option<Duration>,
+		callback: Box<FnMut()>,
+	) -> Timer
+	{
+		let active = Rc::new(Cell::new(true));
+		self.entries.borrow_mut().push(
+			TimerEntry
+			{
+				deadline: Instant::now() + delay,
+				interval,
+				active: active.clone(),
+				callback,
+			}
+		);
+		Timer { active }
+	}
+
+	/// how long until the next timer is due, or `None` if there are no timers
+	pub(crate) fn next_timeout(&self) -> Option<Duration>
+	{
+		let mut entries = self.entries.borrow_mut();
+		entries.retain(|e| e.active.get());
+
+		let now = Instant::now();
+		entries
+			.iter()
+			.map(|e| e.deadline)
+			.min()
+			.map(|d| if d > now { d - now } else { Duration::from_secs(0) })
+	}
+
+	/// call the callbacks of the timers that are due
+	pub(crate) fn run_due(&self)
+	{
+		let now = Instant::now();
+
+		// take the due timers out first, so that callbacks
+		// can start and cancel timers
+		let due : Vec<TimerEntry>;
+		{
+			let mut entries = self.entries.borrow_mut();
+			let all = std::mem::replace(&mut *entries, vec!());
+			let (d, rest) = all.into_iter().partition(|e| e.deadline <= now);
+			*entries = rest;
+			due = d;
+		}
+
+		for mut e in due
+		{
+			// an earlier callback may have cancelled this one
+			if !e.active.get() { continue; }
+
+			(e.callback)();
+
+			match e.interval
+			{
+				Some(interval) if e.active.get() =>
+				{
+					e.deadline += interval;
+					// don't try to catch up on missed ticks
+					if e.deadline <= now
+					{
+						e.deadline = now + interval;
+					}
+					self.entries.borrow_mut().push(e);
+				},
+				_ => e.active.set(false),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn counter() -> (Rc<Cell<u32>>, Box<FnMut()>)
+	{
+		let n = Rc::new(Cell::new(0));
+		let c = n.clone();
+		(n, Box::new(move || c.set(c.get()+1)))
+	}
+
+	#[test]
+	fn single_shot_runs_once()
+	{
+		let timers = Timers::new();
+		let (n, f) = counter();
+		let t = timers.add(Duration::from_secs(0), None, f);
+
+		timers.run_due();
+		timers.run_due();
+		assert_eq!(n.get(), 1);
+		assert!(!t.is_active());
+		assert_eq!(timers.next_timeout(), None);
+	}
+
+	#[test]
+	fn not_due_yet()
+	{
+		let timers = Timers::new();
+		let (n, f) = counter();
+		let t = timers.add(Duration::from_secs(60), None, f);
+
+		timers.run_due();
+		assert_eq!(n.get(), 0);
+		assert!(t.is_active());
+		let timeout = timers.next_timeout().unwrap();
+		assert!(timeout > Duration::from_secs(59) && timeout <= Duration::from_secs(60));
+	}
+
+	#[test]
+	fn interval_repeats()
+	{
+		let timers = Timers::new();
+		let (n, f) = counter();
+		let t = timers.add(Duration::from_secs(0), Some(Duration::from_secs(60)), f);
+
+		timers.run_due();
+		assert_eq!(n.get(), 1);
+		assert!(t.is_active());
+		assert!(timers.next_timeout().unwrap() > Duration::from_secs(59));
+	}
+
+	#[test]
+	fn cancelled()
+	{
+		let timers = Timers::new();
+		let (n, f) = counter();
+		let t = timers.add(Duration::from_secs(0), Some(Duration::from_secs(0)), f);
+		t.cancel();
+
+		timers.run_due();
+		assert_eq!(n.get(), 0);
+		assert_eq!(timers.next_timeout(), None);
+	}
+
+	#[test]
+	fn callback_adds_and_cancels()
+	{
+		let timers = Rc::new(Timers::new());
+		let (n, f) = counter();
+		let later = timers.add(Duration::from_secs(0), None, f);
+
+		// the first callback cancels the second and starts another
+		let started = Rc::new(RefCell::new(None));
+		{
+			let timers2 = timers.clone();
+			let started = started.clone();
+			timers.add(
+				Duration::from_secs(0),
+				None,
+				Box::new(
+					move ||
+					{
+						later.cancel();
+						*started.borrow_mut() =
+							Some(timers2.add(Duration::from_secs(60), None, Box::new(|| {})));
+					}
+				),
+			);
+		}
+		// make the canceller run first
+		timers.entries.borrow_mut().reverse();
+
+		timers.run_due();
+		assert_eq!(n.get(), 0);
+		assert!(started.borrow().as_ref().unwrap().is_active());
+		assert!(timers.next_timeout().unwrap() > Duration::from_secs(59));
+	}
+}
@@ -1,9 +1,11 @@
 use crate::*;
+use std::cell::RefCell;
 
 pub struct LineEdit
 {
 	widget : WidgetBase,
-	text : String,
+	text : RefCell<String>,
+	text_changed : Signal<String>,
 }
 
 impl Widget for LineEdit
@@ -16,10 +18,36 @@ impl Widget for LineEdit
 	{
 		&mut self.widget
 	}
-	fn mouse_event(&self, _e: MouseEvent, _pos: &Point)
+	fn mouse_event(&self, e: MouseEvent, _pos: &Point)
 	{
-		println!("got the event");
+		match e.action
+		{
+			MouseAction::Press(_) => {},
+			_ => return,
+		}
+		self.repaint();
+	}
+
+	fn key_event(&self, e: &KeyEvent) -> bool
+	{
+		if !e.is_press() { return false; }
+
+		if e.keysym == keysym::BACKSPACE
+		{
+			if self.text.borrow_mut().pop().is_none()
+				{ return true; }
+		}
+		else if !e.text.is_empty() && !e.modifiers.control && !e.modifiers.alt
+		{
+			self.text.borrow_mut().push_str(&e.text);
+		}
+		else
+		{
+			return false;
+		}
 		self.repaint();
+		self.text_changed.emit(&self.text());
+		true
 	}
 
 	fn draw(&self, draw : &mut cairo::Cairo)
@@ -234,7 +262,12 @@ impl Widget for LineEdit
 		}
 		draw.set_color(Color::black());
 		draw.move_to(0.0, (height-5) as f64);
-		draw.show_text(&self.text);
+		draw.show_text(&self.text.borrow());
+
+		if self.has_focus()
+		{
+			draw.focus_rect(3, 3, width-6, height-6);
+		}
 	}
 }
 
@@ -246,16 +279,35 @@ impl LineEdit
 		let mut w = LineEdit
 		{
 			widget : WidgetBase::named("LineEdit"),
-			text : "".into(),
+			text : RefCell::new("".into()),
+			text_changed : Signal::new(),
 		};
 		
 		w.widget.set_maximum_size(Size{ width:u32::max_value(), height:22 });
+		w.set_focusable(true);
 		w
 	}
 	
-	pub fn set_text(&mut self, text : String)
+	pub fn set_text(&self, text : String)
+	{
+		let changed = *self.text.borrow() != text;
+		self.text.replace(text);
+		self.repaint();
+		if changed
+		{
+			self.text_changed.emit(&self.text());
+		}
+	}
+
+	/// emitted with the new text when it changes
+	pub fn text_changed(&self) -> &Signal<String>
+	{
+		&self.text_changed
+	}
+
+	pub fn text(&self) -> String
 	{
-		self.text = text;
+		self.text.borrow().clone()
 	}
 }
 